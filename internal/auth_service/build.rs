@@ -0,0 +1,10 @@
+use std::path::PathBuf;
+
+fn main() {
+    // The descriptor set feeds gRPC server reflection
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("auth_service_descriptor.bin"))
+        .compile_protos(&["../../proto/auth_service.proto"], &["../../proto"])
+        .unwrap();
+}
@@ -0,0 +1,43 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use tonic::Status;
+
+/// A valid hash with the same Argon2 parameters as real ones, verified when a login names an
+/// unknown user so the response takes as long as a wrong password would.
+const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$C41Qr7Z+x9dpGtl8YKhGog$cquFQz9J65mKOp7u1D6DjGK40JZ8jaH9MdL3kn3Xha4";
+
+/// Hashes a password with Argon2id and a random salt, producing a PHC string.
+///
+/// Argon2 is deliberately slow, so this runs on the blocking thread pool.
+pub async fn hash_password(password: String) -> Result<String, Status> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    })
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?
+    .map_err(|e| Status::internal(format!("failed to hash password: {}", e)))
+}
+
+pub async fn verify_password(password: String, password_hash: String) -> Result<bool, Status> {
+    tokio::task::spawn_blocking(move || {
+        let parsed = PasswordHash::new(&password_hash)?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok())
+    })
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?
+    .map_err(|e: argon2::password_hash::Error| {
+        Status::internal(format!("stored password hash is invalid: {}", e))
+    })
+}
+
+/// Burns the same Argon2 work as [`verify_password`] without a stored hash to compare against.
+pub async fn verify_dummy_password(password: String) -> Result<(), Status> {
+    verify_password(password, DUMMY_HASH.to_string()).await.map(|_| ())
+}
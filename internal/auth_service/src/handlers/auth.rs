@@ -0,0 +1,341 @@
+use crate::auth::auth_service_server::AuthService;
+use crate::auth::{
+    GenerateTokensRequest, GenerateTokensResponse, LoginRequest, LoginResponse, LogoutRequest,
+    LogoutResponse, RefreshRequest, RefreshResponse, RegisterRequest, RegisterResponse,
+    ValidateRequest, ValidateResponse,
+};
+use crate::crypto::password::{hash_password, verify_dummy_password, verify_password};
+use crate::storage::store::{User, UserStore};
+use crate::tokens::sessions::{now, SessionManager};
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+const DEFAULT_ROLES: &[&str] = &["user"];
+
+/// Usernames are stored trimmed, so every lookup must trim the same way.
+fn normalize_username(username: &str) -> String {
+    username.trim().to_string()
+}
+
+pub struct AuthHandler {
+    users: Arc<dyn UserStore>,
+    sessions: Arc<SessionManager>,
+}
+
+impl AuthHandler {
+    pub fn new(users: Arc<dyn UserStore>, sessions: Arc<SessionManager>) -> Self {
+        Self { users, sessions }
+    }
+}
+
+#[tonic::async_trait]
+impl AuthService for AuthHandler {
+    async fn login(
+        &self,
+        request: Request<LoginRequest>,
+    ) -> Result<Response<LoginResponse>, Status> {
+        let req = request.into_inner();
+
+        let username = normalize_username(&req.username);
+        let user = self.users.find_by_username(&username).await?;
+        let verified = match &user {
+            Some(user) => verify_password(req.password, user.password_hash.clone()).await?,
+            None => {
+                verify_dummy_password(req.password).await?;
+                false
+            }
+        };
+        let user = match user {
+            Some(user) if verified => user,
+            _ => return Err(Status::unauthenticated("invalid username or password")),
+        };
+
+        let tokens = self.sessions.issue(&user.id, &user.roles)?;
+
+        Ok(Response::new(LoginResponse {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_at: tokens.expires_at,
+        }))
+    }
+
+    async fn refresh(
+        &self,
+        request: Request<RefreshRequest>,
+    ) -> Result<Response<RefreshResponse>, Status> {
+        let req = request.into_inner();
+
+        let tokens = self
+            .sessions
+            .refresh(&req.refresh_token, self.users.as_ref())
+            .await?
+            .ok_or_else(|| Status::unauthenticated("invalid or expired refresh token"))?;
+
+        Ok(Response::new(RefreshResponse {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_at: tokens.expires_at,
+        }))
+    }
+
+    async fn validate(
+        &self,
+        request: Request<ValidateRequest>,
+    ) -> Result<Response<ValidateResponse>, Status> {
+        let req = request.into_inner();
+
+        let response = match self.sessions.validate(&req.access_token) {
+            Some(claims) => ValidateResponse {
+                valid: true,
+                user_id: claims.sub,
+                roles: claims.roles,
+                expires_at: claims.exp,
+            },
+            None => ValidateResponse::default(),
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn logout(
+        &self,
+        request: Request<LogoutRequest>,
+    ) -> Result<Response<LogoutResponse>, Status> {
+        let req = request.into_inner();
+
+        let user = self
+            .users
+            .find_by_username(&normalize_username(&req.username))
+            .await?
+            .ok_or_else(|| Status::unauthenticated("unknown user"))?;
+
+        match self.sessions.validate(&req.access_token) {
+            Some(claims) if claims.sub == user.id => {
+                self.sessions.revoke(&claims);
+            }
+            _ => return Err(Status::unauthenticated("access token does not belong to this user")),
+        }
+
+        let message = if req.all_sessions {
+            self.sessions.revoke_all(&user.id);
+            "Successfully logged out of all sessions"
+        } else {
+            "Successfully logged out"
+        };
+
+        Ok(Response::new(LogoutResponse {
+            success: true,
+            message: message.to_string(),
+        }))
+    }
+
+    async fn register(
+        &self,
+        request: Request<RegisterRequest>,
+    ) -> Result<Response<RegisterResponse>, Status> {
+        let req = request.into_inner();
+
+        let username = normalize_username(&req.username);
+        let email = req.email.trim().to_lowercase();
+        if username.is_empty() {
+            return Err(Status::invalid_argument("username must not be empty"));
+        }
+        if !email.contains('@') {
+            return Err(Status::invalid_argument("email is not valid"));
+        }
+        if req.password.is_empty() {
+            return Err(Status::invalid_argument("password must not be empty"));
+        }
+
+        let user = self
+            .users
+            .create_user(User {
+                id: uuid::Uuid::new_v4().to_string(),
+                username,
+                email,
+                password_hash: hash_password(req.password).await?,
+                roles: DEFAULT_ROLES.iter().map(|role| role.to_string()).collect(),
+                created_at: now(),
+            })
+            .await?;
+
+        log::info!("Registered user {} ({})", user.username, user.id);
+
+        let tokens = self.sessions.issue(&user.id, &user.roles)?;
+
+        Ok(Response::new(RegisterResponse {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_at: tokens.expires_at,
+        }))
+    }
+
+    async fn generate_tokens(
+        &self,
+        request: Request<GenerateTokensRequest>,
+    ) -> Result<Response<GenerateTokensResponse>, Status> {
+        let req = request.into_inner();
+
+        let user = self
+            .users
+            .find_by_id(&req.user_id)
+            .await?
+            .ok_or_else(|| Status::not_found("user not found"))?;
+
+        let roles = if req.roles.is_empty() { user.roles } else { req.roles };
+        let tokens = self.sessions.issue(&user.id, &roles)?;
+
+        Ok(Response::new(GenerateTokensResponse {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_at: tokens.expires_at,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryUserStore;
+    use crate::tokens::jwt::JwtKeys;
+    use tonic::Code;
+
+    async fn handler_with_alice() -> AuthHandler {
+        let handler = AuthHandler::new(
+            Arc::new(MemoryUserStore::default()),
+            Arc::new(SessionManager::new(JwtKeys::ephemeral())),
+        );
+        handler
+            .register(Request::new(RegisterRequest {
+                username: " alice ".to_string(),
+                password: "correct horse".to_string(),
+                email: "alice@example.com".to_string(),
+            }))
+            .await
+            .unwrap();
+        handler
+    }
+
+    fn login_request(username: &str, password: &str) -> Request<LoginRequest> {
+        Request::new(LoginRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn login_trims_the_username_like_register() {
+        let handler = handler_with_alice().await;
+
+        let response = handler.login(login_request("alice  ", "correct horse")).await;
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    async fn unknown_user_and_wrong_password_fail_the_same_way() {
+        let handler = handler_with_alice().await;
+
+        let unknown = handler.login(login_request("bob", "correct horse")).await.unwrap_err();
+        let wrong = handler.login(login_request("alice", "wrong")).await.unwrap_err();
+
+        assert_eq!(unknown.code(), Code::Unauthenticated);
+        assert_eq!(wrong.code(), Code::Unauthenticated);
+        assert_eq!(unknown.message(), wrong.message());
+    }
+
+    async fn login(handler: &AuthHandler) -> LoginResponse {
+        handler
+            .login(login_request("alice", "correct horse"))
+            .await
+            .unwrap()
+            .into_inner()
+    }
+
+    async fn is_valid(handler: &AuthHandler, access_token: &str) -> bool {
+        handler
+            .validate(Request::new(ValidateRequest {
+                access_token: access_token.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .valid
+    }
+
+    async fn refreshes(handler: &AuthHandler, refresh_token: &str) -> bool {
+        handler
+            .refresh(Request::new(RefreshRequest {
+                refresh_token: refresh_token.to_string(),
+            }))
+            .await
+            .is_ok()
+    }
+
+    async fn logout(handler: &AuthHandler, access_token: &str, all_sessions: bool) {
+        handler
+            .logout(Request::new(LogoutRequest {
+                username: "alice".to_string(),
+                access_token: access_token.to_string(),
+                all_sessions,
+            }))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_only_its_own_session() {
+        let handler = handler_with_alice().await;
+        let laptop = login(&handler).await;
+        let phone = login(&handler).await;
+
+        logout(&handler, &laptop.access_token, false).await;
+
+        assert!(!is_valid(&handler, &laptop.access_token).await);
+        assert!(is_valid(&handler, &phone.access_token).await);
+        assert!(refreshes(&handler, &phone.refresh_token).await);
+    }
+
+    #[tokio::test]
+    async fn logout_of_all_sessions_revokes_every_family() {
+        let handler = handler_with_alice().await;
+        let laptop = login(&handler).await;
+        let phone = login(&handler).await;
+
+        logout(&handler, &laptop.access_token, true).await;
+
+        for session in [&laptop, &phone] {
+            assert!(!is_valid(&handler, &session.access_token).await);
+            assert!(!refreshes(&handler, &session.refresh_token).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn logout_rejects_another_users_token() {
+        let handler = handler_with_alice().await;
+        let alice = login(&handler).await;
+        handler
+            .register(Request::new(RegisterRequest {
+                username: "bob".to_string(),
+                password: "battery staple".to_string(),
+                email: "bob@example.com".to_string(),
+            }))
+            .await
+            .unwrap();
+
+        let err = handler
+            .logout(Request::new(LogoutRequest {
+                username: "bob".to_string(),
+                access_token: alice.access_token.clone(),
+                all_sessions: true,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+        assert!(is_valid(&handler, &alice.access_token).await);
+    }
+
+    #[tokio::test]
+    async fn dummy_hash_is_a_valid_argon2_hash() {
+        verify_dummy_password("anything".to_string()).await.unwrap();
+    }
+}
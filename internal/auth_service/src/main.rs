@@ -0,0 +1,33 @@
+mod crypto;
+mod handlers;
+mod server;
+mod storage;
+mod tokens;
+
+use config::load_config;
+use logger::init_logger;
+use server::service::run_server;
+use std::error::Error;
+
+pub mod auth {
+    tonic::include_proto!("auth_service");
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("auth_service_descriptor");
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    grpc_services::install_crypto_provider()?;
+
+    let config = load_config("auth_service")?;
+
+    init_logger(&config.log_level);
+
+    log::info!(
+        "Service {} started on {}",
+        config.service_name,
+        config.address
+    );
+
+    run_server(config).await
+}
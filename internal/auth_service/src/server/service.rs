@@ -0,0 +1,47 @@
+use crate::auth::auth_service_server::AuthServiceServer;
+use crate::auth::FILE_DESCRIPTOR_SET;
+use crate::handlers::auth::AuthHandler;
+use crate::storage::store::open_store;
+use crate::tokens::jwt::JwtKeys;
+use crate::tokens::sessions::SessionManager;
+use config::config::AppConfig;
+use grpc_services::{server_tls_config, standard_services};
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::transport::Server;
+
+use std::error::Error;
+
+const SESSION_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub async fn run_server(config: AppConfig) -> Result<(), Box<dyn Error>> {
+    let users = open_store(&config)?;
+    let jwt = match &config.jwt {
+        Some(jwt_config) => JwtKeys::from_config(jwt_config)?,
+        None => {
+            log::warn!("No [jwt] section configured, signing tokens with an ephemeral key");
+            JwtKeys::ephemeral()
+        }
+    };
+    let sessions = Arc::new(SessionManager::new(jwt));
+    sessions.spawn_pruner(SESSION_PRUNE_INTERVAL);
+    let auth_service = AuthHandler::new(users, sessions);
+    let (health, standard_routes) = standard_services(&[FILE_DESCRIPTOR_SET])?;
+    health.set_serving::<AuthServiceServer<AuthHandler>>().await;
+
+    log::info!("{} running on {}", config.service_name, config.address);
+
+    let mut server = Server::builder();
+    if let Some(tls) = server_tls_config(&config)? {
+        log::info!("Serving gRPC over TLS");
+        server = server.tls_config(tls)?;
+    }
+
+    server
+        .add_routes(standard_routes)
+        .add_service(AuthServiceServer::new(auth_service))
+        .serve(config.address)
+        .await?;
+
+    Ok(())
+}
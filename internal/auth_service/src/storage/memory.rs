@@ -0,0 +1,75 @@
+use super::store::{StoreError, User, UserStore};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+#[derive(Debug, Default)]
+pub struct MemoryUserStore {
+    users: RwLock<HashMap<String, User>>,
+}
+
+#[tonic::async_trait]
+impl UserStore for MemoryUserStore {
+    async fn create_user(&self, user: User) -> Result<User, StoreError> {
+        let mut users = self.users.write().map_err(|e| StoreError::Backend(e.to_string()))?;
+
+        for existing in users.values() {
+            if existing.username.eq_ignore_ascii_case(&user.username) {
+                return Err(StoreError::UsernameTaken);
+            }
+            if existing.email.eq_ignore_ascii_case(&user.email) {
+                return Err(StoreError::EmailTaken);
+            }
+        }
+
+        users.insert(user.id.clone(), user.clone());
+        Ok(user)
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, StoreError> {
+        let users = self.users.read().map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(users
+            .values()
+            .find(|user| user.username.eq_ignore_ascii_case(username))
+            .cloned())
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, StoreError> {
+        let users = self.users.read().map_err(|e| StoreError::Backend(e.to_string()))?;
+        Ok(users.get(id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: &str, username: &str, email: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: username.to_string(),
+            email: email.to_string(),
+            password_hash: String::new(),
+            roles: vec![],
+            created_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_a_taken_username_regardless_of_case() {
+        let store = MemoryUserStore::default();
+        store.create_user(user("1", "alice", "alice@example.com")).await.unwrap();
+
+        let err = store.create_user(user("2", "ALICE", "other@example.com")).await.unwrap_err();
+        assert!(matches!(err, StoreError::UsernameTaken));
+        assert!(store.find_by_id("2").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_taken_email_regardless_of_case() {
+        let store = MemoryUserStore::default();
+        store.create_user(user("1", "alice", "alice@example.com")).await.unwrap();
+
+        let err = store.create_user(user("2", "bob", "Alice@Example.com")).await.unwrap_err();
+        assert!(matches!(err, StoreError::EmailTaken));
+    }
+}
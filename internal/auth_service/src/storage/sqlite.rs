@@ -0,0 +1,198 @@
+use super::store::{StoreError, User, UserStore};
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, Row};
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS users (
+        id            TEXT PRIMARY KEY,
+        username      TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        email         TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        password_hash TEXT NOT NULL,
+        roles         TEXT NOT NULL,
+        created_at    INTEGER NOT NULL
+    );
+";
+
+const USER_COLUMNS: &str = "id, username, email, password_hash, roles, created_at";
+
+/// SQLite-backed store. rusqlite is blocking, so every query runs on the blocking thread pool.
+#[derive(Clone)]
+pub struct SqliteUserStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteUserStore {
+    pub fn open(path: &str) -> Result<Self, StoreError> {
+        let conn = Connection::open(path).map_err(backend)?;
+        conn.execute_batch(SCHEMA).map_err(backend)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, StoreError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, StoreError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().map_err(|e| StoreError::Backend(e.to_string()))?;
+            f(&conn)
+        })
+        .await
+        .map_err(|e| StoreError::Backend(e.to_string()))?
+    }
+}
+
+#[tonic::async_trait]
+impl UserStore for SqliteUserStore {
+    async fn create_user(&self, user: User) -> Result<User, StoreError> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO users (id, username, email, password_hash, roles, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    user.id,
+                    user.username,
+                    user.email,
+                    user.password_hash,
+                    user.roles.join(","),
+                    user.created_at,
+                ],
+            )
+            .map_err(unique_violation)?;
+            Ok(user)
+        })
+        .await
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, StoreError> {
+        let username = username.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM users WHERE username = ?1", USER_COLUMNS),
+                params![username],
+                user_from_row,
+            )
+            .optional()
+            .map_err(backend)
+        })
+        .await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, StoreError> {
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS),
+                params![id],
+                user_from_row,
+            )
+            .optional()
+            .map_err(backend)
+        })
+        .await
+    }
+}
+
+fn user_from_row(row: &Row<'_>) -> rusqlite::Result<User> {
+    let roles: String = row.get(4)?;
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        email: row.get(2)?,
+        password_hash: row.get(3)?,
+        roles: roles
+            .split(',')
+            .filter(|role| !role.is_empty())
+            .map(str::to_string)
+            .collect(),
+        created_at: row.get(5)?,
+    })
+}
+
+fn unique_violation(err: rusqlite::Error) -> StoreError {
+    if let rusqlite::Error::SqliteFailure(ref failure, Some(ref message)) = err {
+        if failure.code == ErrorCode::ConstraintViolation {
+            if message.contains("users.username") {
+                return StoreError::UsernameTaken;
+            }
+            if message.contains("users.email") {
+                return StoreError::EmailTaken;
+            }
+        }
+    }
+    backend(err)
+}
+
+fn backend(err: rusqlite::Error) -> StoreError {
+    StoreError::Backend(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryUserStore;
+    use tonic::{Code, Status};
+
+    fn user(id: &str, username: &str, email: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: username.to_string(),
+            email: email.to_string(),
+            password_hash: String::new(),
+            roles: vec!["user".to_string()],
+            created_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_a_taken_username_regardless_of_case() {
+        let store = SqliteUserStore::open(":memory:").unwrap();
+        store.create_user(user("1", "alice", "alice@example.com")).await.unwrap();
+
+        let err = store.create_user(user("2", "ALICE", "other@example.com")).await.unwrap_err();
+        assert!(matches!(err, StoreError::UsernameTaken));
+        assert!(store.find_by_id("2").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_taken_email_regardless_of_case() {
+        let store = SqliteUserStore::open(":memory:").unwrap();
+        store.create_user(user("1", "alice", "alice@example.com")).await.unwrap();
+
+        let err = store.create_user(user("2", "bob", "Alice@Example.com")).await.unwrap_err();
+        assert!(matches!(err, StoreError::EmailTaken));
+    }
+
+    #[tokio::test]
+    async fn unique_violations_match_the_memory_store() {
+        let sqlite = SqliteUserStore::open(":memory:").unwrap();
+        let memory = MemoryUserStore::default();
+        for store in [&sqlite as &dyn UserStore, &memory] {
+            store.create_user(user("1", "alice", "alice@example.com")).await.unwrap();
+        }
+
+        for duplicate in [user("2", "alice", "bob@example.com"), user("2", "bob", "alice@example.com")] {
+            let from_sqlite = sqlite.create_user(duplicate.clone()).await.unwrap_err();
+            let from_memory = memory.create_user(duplicate).await.unwrap_err();
+            assert_eq!(from_sqlite.to_string(), from_memory.to_string());
+
+            let (sqlite_status, memory_status) = (Status::from(from_sqlite), Status::from(from_memory));
+            assert_eq!(sqlite_status.code(), Code::AlreadyExists);
+            assert_eq!(sqlite_status.code(), memory_status.code());
+            assert_eq!(sqlite_status.message(), memory_status.message());
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trips_users() {
+        let store = SqliteUserStore::open(":memory:").unwrap();
+        store.create_user(user("1", "alice", "alice@example.com")).await.unwrap();
+
+        let found = store.find_by_username("Alice").await.unwrap().unwrap();
+        assert_eq!(found.id, "1");
+        assert_eq!(found.roles, vec!["user".to_string()]);
+    }
+}
@@ -0,0 +1,63 @@
+use super::memory::MemoryUserStore;
+use super::sqlite::SqliteUserStore;
+use config::AppConfig;
+use std::sync::Arc;
+use thiserror::Error;
+use tonic::Status;
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub password_hash: String,
+    pub roles: Vec<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("username is already taken")]
+    UsernameTaken,
+
+    #[error("email is already registered")]
+    EmailTaken,
+
+    #[error("storage backend error: {0}")]
+    Backend(String),
+}
+
+impl From<StoreError> for Status {
+    fn from(err: StoreError) -> Self {
+        match err {
+            StoreError::UsernameTaken | StoreError::EmailTaken => Status::already_exists(err.to_string()),
+            StoreError::Backend(_) => {
+                log::error!("{}", err);
+                Status::internal("storage backend error")
+            }
+        }
+    }
+}
+
+/// Persistence for registered users. Implementations must reject a user whose username or
+/// email is already present, comparing both case-insensitively.
+#[tonic::async_trait]
+pub trait UserStore: Send + Sync {
+    async fn create_user(&self, user: User) -> Result<User, StoreError>;
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, StoreError>;
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, StoreError>;
+}
+
+/// Builds the store selected by the `[storage]` config section, defaulting to in-memory.
+pub fn open_store(config: &AppConfig) -> Result<Arc<dyn UserStore>, StoreError> {
+    match config.storage_backend.as_deref().unwrap_or("memory") {
+        "memory" => Ok(Arc::new(MemoryUserStore::default())),
+        "sqlite" => {
+            let path = config.storage_path.as_deref().unwrap_or("auth_service.db");
+            Ok(Arc::new(SqliteUserStore::open(path)?))
+        }
+        other => Err(StoreError::Backend(format!("unknown storage backend '{}'", other))),
+    }
+}
@@ -0,0 +1,315 @@
+use config::{JwtConfig, JwtKeyConfig};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use tonic::Status;
+
+const DEFAULT_ACCESS_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub roles: Vec<String>,
+    pub exp: i64,
+    pub iat: i64,
+    pub jti: String,
+}
+
+#[derive(Debug, Error)]
+pub enum JwtError {
+    #[error("jwt key '{kid}': {message}")]
+    Key { kid: String, message: String },
+
+    #[error("signing key '{0}' is not configured or has no private key")]
+    MissingSigningKey(String),
+
+    #[error("token has no 'kid' header")]
+    MissingKid,
+
+    #[error("token was signed with unknown key '{0}'")]
+    UnknownKid(String),
+
+    #[error(transparent)]
+    Token(#[from] jsonwebtoken::errors::Error),
+}
+
+impl From<JwtError> for Status {
+    fn from(err: JwtError) -> Self {
+        log::error!("{}", err);
+        Status::internal("failed to issue token")
+    }
+}
+
+struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+struct VerifyingKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Signs access tokens with the active key and verifies them against every configured key id.
+///
+/// Rotation works by adding a new key, switching `signing_kid` to it and keeping the previous
+/// key in the list until the tokens it signed have expired.
+pub struct JwtKeys {
+    signing: SigningKey,
+    verifying: HashMap<String, VerifyingKey>,
+    access_token_ttl: Duration,
+}
+
+impl JwtKeys {
+    pub fn from_config(config: &JwtConfig) -> Result<Self, JwtError> {
+        let mut signing = None;
+        let mut verifying = HashMap::new();
+
+        for key_config in &config.keys {
+            let algorithm = Algorithm::from_str(&key_config.algorithm).map_err(|_| JwtError::Key {
+                kid: key_config.kid.clone(),
+                message: format!("unsupported algorithm '{}'", key_config.algorithm),
+            })?;
+            let (encoding, decoding) = load_key(key_config, algorithm).map_err(|message| JwtError::Key {
+                kid: key_config.kid.clone(),
+                message,
+            })?;
+
+            if key_config.kid == config.signing_kid {
+                signing = encoding.map(|key| SigningKey {
+                    kid: key_config.kid.clone(),
+                    algorithm,
+                    key,
+                });
+            }
+            verifying.insert(key_config.kid.clone(), VerifyingKey { algorithm, key: decoding });
+        }
+
+        Ok(Self {
+            signing: signing.ok_or_else(|| JwtError::MissingSigningKey(config.signing_kid.clone()))?,
+            verifying,
+            access_token_ttl: config
+                .access_token_ttl_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_ACCESS_TOKEN_TTL),
+        })
+    }
+
+    /// HS256 key with a random per-process secret, for running without a `[jwt]` section.
+    /// Tokens do not survive a restart and cannot be verified by other instances.
+    pub fn ephemeral() -> Self {
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        let kid = "ephemeral".to_string();
+
+        Self {
+            signing: SigningKey {
+                kid: kid.clone(),
+                algorithm: Algorithm::HS256,
+                key: EncodingKey::from_secret(secret.as_bytes()),
+            },
+            verifying: HashMap::from([(
+                kid,
+                VerifyingKey {
+                    algorithm: Algorithm::HS256,
+                    key: DecodingKey::from_secret(secret.as_bytes()),
+                },
+            )]),
+            access_token_ttl: DEFAULT_ACCESS_TOKEN_TTL,
+        }
+    }
+
+    pub fn access_token_ttl(&self) -> Duration {
+        self.access_token_ttl
+    }
+
+    pub fn sign(&self, claims: &Claims) -> Result<String, JwtError> {
+        let mut header = Header::new(self.signing.algorithm);
+        header.kid = Some(self.signing.kid.clone());
+        Ok(encode(&header, claims, &self.signing.key)?)
+    }
+
+    /// Checks the signature with the key named by the token's `kid` and rejects tokens that are
+    /// expired or not yet valid.
+    pub fn verify(&self, token: &str) -> Result<Claims, JwtError> {
+        let header = decode_header(token)?;
+        let kid = header.kid.ok_or(JwtError::MissingKid)?;
+        let key = self.verifying.get(&kid).ok_or(JwtError::UnknownKid(kid))?;
+
+        // Pinning the algorithm to the key stops a token from picking its own verification scheme
+        let mut validation = Validation::new(key.algorithm);
+        validation.leeway = 0;
+        validation.validate_nbf = true;
+        validation.set_required_spec_claims(&["exp", "sub"]);
+
+        Ok(decode::<Claims>(token, &key.key, &validation)?.claims)
+    }
+}
+
+fn load_key(config: &JwtKeyConfig, algorithm: Algorithm) -> Result<(Option<EncodingKey>, DecodingKey), String> {
+    match algorithm {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+            let secret = match (&config.secret, &config.secret_env) {
+                (Some(secret), _) => secret.clone(),
+                (None, Some(var)) => std::env::var(var).map_err(|_| format!("environment variable {} is not set", var))?,
+                (None, None) => return Err("HMAC keys need 'secret' or 'secret_env'".to_string()),
+            };
+            Ok((
+                Some(EncodingKey::from_secret(secret.as_bytes())),
+                DecodingKey::from_secret(secret.as_bytes()),
+            ))
+        }
+        Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512 => {
+            load_pem_pair(config, EncodingKey::from_rsa_pem, DecodingKey::from_rsa_pem)
+        }
+        Algorithm::ES256 | Algorithm::ES384 => load_pem_pair(config, EncodingKey::from_ec_pem, DecodingKey::from_ec_pem),
+        Algorithm::EdDSA => load_pem_pair(config, EncodingKey::from_ed_pem, DecodingKey::from_ed_pem),
+    }
+}
+
+/// Asymmetric keys need the public key for verification; the private key is only required on
+/// the key that signs, so retired keys can be kept as public keys alone.
+fn load_pem_pair(
+    config: &JwtKeyConfig,
+    encoding: fn(&[u8]) -> jsonwebtoken::errors::Result<EncodingKey>,
+    decoding: fn(&[u8]) -> jsonwebtoken::errors::Result<DecodingKey>,
+) -> Result<(Option<EncodingKey>, DecodingKey), String> {
+    let public_path = config
+        .public_key_path
+        .as_ref()
+        .ok_or_else(|| "asymmetric keys need 'public_key_path'".to_string())?;
+    let public_pem = std::fs::read(public_path).map_err(|e| format!("failed to read {}: {}", public_path, e))?;
+    let decoding_key = decoding(&public_pem).map_err(|e| format!("invalid public key {}: {}", public_path, e))?;
+
+    let encoding_key = match &config.private_key_path {
+        Some(path) => {
+            let pem = std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+            Some(encoding(&pem).map_err(|e| format!("invalid private key {}: {}", path, e))?)
+        }
+        None => None,
+    };
+
+    Ok((encoding_key, decoding_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::sessions::now;
+    use jsonwebtoken::errors::ErrorKind;
+    use serde_json::json;
+
+    fn hmac_key(kid: &str, secret: &str) -> JwtKeyConfig {
+        JwtKeyConfig {
+            kid: kid.to_string(),
+            algorithm: "HS256".to_string(),
+            secret: Some(secret.to_string()),
+            secret_env: None,
+            private_key_path: None,
+            public_key_path: None,
+        }
+    }
+
+    fn keys(signing_kid: &str) -> JwtKeys {
+        JwtKeys::from_config(&JwtConfig {
+            signing_kid: signing_kid.to_string(),
+            access_token_ttl_secs: None,
+            keys: vec![hmac_key("old", "old-secret"), hmac_key("new", "new-secret")],
+        })
+        .unwrap()
+    }
+
+    fn claims(exp: i64) -> Claims {
+        Claims {
+            sub: "user-1".to_string(),
+            roles: vec!["admin".to_string()],
+            exp,
+            iat: now(),
+            jti: "jti-1".to_string(),
+        }
+    }
+
+    /// Signs arbitrary claims with an arbitrary header, as an attacker or another issuer could.
+    fn forge(algorithm: Algorithm, kid: Option<&str>, secret: &str, claims: &serde_json::Value) -> String {
+        let mut header = Header::new(algorithm);
+        header.kid = kid.map(str::to_string);
+        encode(&header, claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    fn is_kind(err: JwtError, expected: ErrorKind) -> bool {
+        matches!(err, JwtError::Token(e) if *e.kind() == expected)
+    }
+
+    #[test]
+    fn signed_tokens_verify() {
+        let keys = keys("new");
+        let token = keys.sign(&claims(now() + 60)).unwrap();
+
+        let verified = keys.verify(&token).unwrap();
+        assert_eq!(verified.sub, "user-1");
+        assert_eq!(verified.roles, vec!["admin".to_string()]);
+        assert_eq!(verified.jti, "jti-1");
+        assert_eq!(decode_header(&token).unwrap().kid.as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn tokens_signed_before_rotation_still_verify() {
+        let token = keys("old").sign(&claims(now() + 60)).unwrap();
+        assert_eq!(keys("new").verify(&token).unwrap().sub, "user-1");
+    }
+
+    #[test]
+    fn rejects_unknown_and_missing_kid() {
+        let claims = json!({"sub": "user-1", "roles": [], "exp": now() + 60, "iat": now(), "jti": "x"});
+
+        let unknown = forge(Algorithm::HS256, Some("retired"), "old-secret", &claims);
+        assert!(matches!(keys("new").verify(&unknown), Err(JwtError::UnknownKid(kid)) if kid == "retired"));
+
+        let missing = forge(Algorithm::HS256, None, "old-secret", &claims);
+        assert!(matches!(keys("new").verify(&missing), Err(JwtError::MissingKid)));
+    }
+
+    #[test]
+    fn rejects_an_algorithm_the_kid_is_not_pinned_to() {
+        let claims = json!({"sub": "user-1", "roles": [], "exp": now() + 60, "iat": now(), "jti": "x"});
+        let token = forge(Algorithm::HS512, Some("new"), "new-secret", &claims);
+
+        let err = keys("new").verify(&token).unwrap_err();
+        assert!(is_kind(err, ErrorKind::InvalidAlgorithm));
+    }
+
+    #[test]
+    fn rejects_a_signature_from_another_key() {
+        let claims = json!({"sub": "user-1", "roles": [], "exp": now() + 60, "iat": now(), "jti": "x"});
+        let token = forge(Algorithm::HS256, Some("new"), "old-secret", &claims);
+
+        let err = keys("new").verify(&token).unwrap_err();
+        assert!(is_kind(err, ErrorKind::InvalidSignature));
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let keys = keys("new");
+        let token = keys.sign(&claims(now() - 1)).unwrap();
+
+        assert!(is_kind(keys.verify(&token).unwrap_err(), ErrorKind::ExpiredSignature));
+    }
+
+    #[test]
+    fn rejects_tokens_that_are_not_yet_valid() {
+        let claims = json!({
+            "sub": "user-1",
+            "roles": [],
+            "exp": now() + 120,
+            "nbf": now() + 60,
+            "iat": now(),
+            "jti": "x",
+        });
+        let token = forge(Algorithm::HS256, Some("new"), "new-secret", &claims);
+
+        assert!(is_kind(keys("new").verify(&token).unwrap_err(), ErrorKind::ImmatureSignature));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod jwt;
+pub mod refresh;
+pub mod revocation;
+pub mod sessions;
@@ -0,0 +1,216 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use super::sessions::now;
+
+const REFRESH_TOKEN_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Tokens are looked up by their SHA-256, so the raw tokens never sit in memory.
+type TokenHash = [u8; 32];
+
+struct RefreshEntry {
+    family_id: String,
+    used: bool,
+    expires_at: i64,
+}
+
+/// Every refresh token descended from one login. Revoking the family invalidates all of them.
+struct Family {
+    user_id: String,
+    revoked: bool,
+    expires_at: i64,
+}
+
+#[derive(Default)]
+struct State {
+    tokens: HashMap<TokenHash, RefreshEntry>,
+    families: HashMap<String, Family>,
+}
+
+pub enum Rotation {
+    Rotated {
+        family_id: String,
+        refresh_token: String,
+        user_id: String,
+    },
+    /// An already rotated token was presented again; its family has been revoked.
+    Reused { family_id: String, user_id: String },
+    Invalid,
+}
+
+/// Opaque, server-side refresh tokens grouped into families.
+///
+/// Used tokens are kept until they expire so a replay can be told apart from an unknown token.
+/// Expired tokens and families are only dropped by `prune`, which the owner runs periodically.
+#[derive(Default)]
+pub struct RefreshTokens {
+    state: RwLock<State>,
+}
+
+impl RefreshTokens {
+    /// Opens a new family for a fresh login and returns its id and first token.
+    pub fn start_family(&self, user_id: &str) -> (String, String) {
+        let now = now();
+        let expires_at = now + REFRESH_TOKEN_TTL.as_secs() as i64;
+        let family_id = uuid::Uuid::new_v4().to_string();
+        let token = new_token();
+
+        let mut state = self.state.write().unwrap();
+        state.families.insert(
+            family_id.clone(),
+            Family {
+                user_id: user_id.to_string(),
+                revoked: false,
+                expires_at,
+            },
+        );
+        state.tokens.insert(
+            hash(&token),
+            RefreshEntry {
+                family_id: family_id.clone(),
+                used: false,
+                expires_at,
+            },
+        );
+
+        (family_id, token)
+    }
+
+    /// Marks the presented token as used and hands out its successor in the same family.
+    pub fn rotate(&self, refresh_token: &str) -> Rotation {
+        let now = now();
+        let mut state = self.state.write().unwrap();
+
+        let (family_id, used) = match state.tokens.get_mut(&hash(refresh_token)) {
+            Some(entry) if entry.expires_at > now => {
+                let used = entry.used;
+                entry.used = true;
+                (entry.family_id.clone(), used)
+            }
+            _ => return Rotation::Invalid,
+        };
+
+        let Some(family) = state.families.get_mut(&family_id) else {
+            return Rotation::Invalid;
+        };
+        if family.revoked {
+            return Rotation::Invalid;
+        }
+        if used {
+            family.revoked = true;
+            return Rotation::Reused {
+                family_id,
+                user_id: family.user_id.clone(),
+            };
+        }
+
+        let expires_at = now + REFRESH_TOKEN_TTL.as_secs() as i64;
+        family.expires_at = expires_at;
+        let user_id = family.user_id.clone();
+
+        let token = new_token();
+        state.tokens.insert(
+            hash(&token),
+            RefreshEntry {
+                family_id: family_id.clone(),
+                used: false,
+                expires_at,
+            },
+        );
+
+        Rotation::Rotated {
+            family_id,
+            refresh_token: token,
+            user_id,
+        }
+    }
+
+    /// Revokes every live family of the user and returns how many there were.
+    pub fn revoke_user(&self, user_id: &str) -> usize {
+        let mut state = self.state.write().unwrap();
+        let mut count = 0;
+        for family in state.families.values_mut() {
+            if family.user_id == user_id && !family.revoked {
+                family.revoked = true;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Drops tokens and families that have expired.
+    pub fn prune(&self, now: i64) {
+        let mut state = self.state.write().unwrap();
+        state.tokens.retain(|_, entry| entry.expires_at > now);
+        state.families.retain(|_, family| family.expires_at > now);
+    }
+}
+
+fn new_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+fn hash(token: &str) -> TokenHash {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotated(rotation: Rotation) -> String {
+        match rotation {
+            Rotation::Rotated { refresh_token, .. } => refresh_token,
+            _ => panic!("expected the token to rotate"),
+        }
+    }
+
+    #[test]
+    fn reusing_a_rotated_token_revokes_the_family() {
+        let tokens = RefreshTokens::default();
+        let (family_id, first) = tokens.start_family("user-1");
+        let second = rotated(tokens.rotate(&first));
+
+        match tokens.rotate(&first) {
+            Rotation::Reused { family_id: reused, user_id } => {
+                assert_eq!(reused, family_id);
+                assert_eq!(user_id, "user-1");
+            }
+            _ => panic!("expected reuse to be detected"),
+        }
+        assert!(matches!(tokens.rotate(&second), Rotation::Invalid));
+    }
+
+    #[test]
+    fn other_families_survive_a_reuse() {
+        let tokens = RefreshTokens::default();
+        let (_, first) = tokens.start_family("user-1");
+        let (_, other) = tokens.start_family("user-1");
+        rotated(tokens.rotate(&first));
+
+        assert!(matches!(tokens.rotate(&first), Rotation::Reused { .. }));
+        rotated(tokens.rotate(&other));
+    }
+
+    #[test]
+    fn pruning_drops_only_expired_families() {
+        let tokens = RefreshTokens::default();
+        let (_, first) = tokens.start_family("user-1");
+        let (_, second) = tokens.start_family("user-2");
+
+        tokens.prune(now());
+        rotated(tokens.rotate(&first));
+
+        tokens.prune(now() + REFRESH_TOKEN_TTL.as_secs() as i64 + 1);
+        assert!(tokens.state.read().unwrap().families.is_empty());
+        assert!(matches!(tokens.rotate(&second), Rotation::Invalid));
+    }
+
+    #[test]
+    fn unknown_token_is_invalid() {
+        let tokens = RefreshTokens::default();
+        assert!(matches!(tokens.rotate("missing"), Rotation::Invalid));
+    }
+}
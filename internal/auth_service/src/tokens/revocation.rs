@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+#[derive(Default)]
+struct State {
+    /// Revoked `jti` -> the token's own `exp`, after which the entry is useless.
+    revoked: HashMap<String, i64>,
+    /// Live `jti`s per user, so every outstanding access token can be revoked at once.
+    issued: HashMap<String, HashMap<String, i64>>,
+    /// Live `jti`s per refresh token family, so a replayed refresh token can take them all down.
+    families: HashMap<String, HashMap<String, i64>>,
+}
+
+/// Access tokens that must be rejected before they expire on their own.
+/// Expired entries are only dropped by `prune`, which the owner runs periodically.
+#[derive(Default)]
+pub struct RevocationList {
+    state: RwLock<State>,
+}
+
+impl RevocationList {
+    pub fn track(&self, user_id: &str, family_id: &str, jti: &str, expires_at: i64) {
+        let mut state = self.state.write().unwrap();
+        state
+            .issued
+            .entry(user_id.to_string())
+            .or_default()
+            .insert(jti.to_string(), expires_at);
+        state
+            .families
+            .entry(family_id.to_string())
+            .or_default()
+            .insert(jti.to_string(), expires_at);
+    }
+
+    pub fn revoke(&self, user_id: &str, jti: &str, expires_at: i64) {
+        let mut state = self.state.write().unwrap();
+        if let Some(tokens) = state.issued.get_mut(user_id) {
+            tokens.remove(jti);
+        }
+        state.revoked.insert(jti.to_string(), expires_at);
+    }
+
+    /// Revokes every access token issued to the user that has not expired yet.
+    pub fn revoke_user(&self, user_id: &str, now: i64) -> usize {
+        let mut state = self.state.write().unwrap();
+        let tokens = state.issued.remove(user_id).unwrap_or_default();
+        state.revoke_live(tokens, now)
+    }
+
+    /// Revokes every access token issued from one refresh token family that has not expired yet.
+    pub fn revoke_family(&self, family_id: &str, now: i64) -> usize {
+        let mut state = self.state.write().unwrap();
+        let tokens = state.families.remove(family_id).unwrap_or_default();
+        state.revoke_live(tokens, now)
+    }
+
+    pub fn is_revoked(&self, jti: &str) -> bool {
+        self.state.read().unwrap().revoked.contains_key(jti)
+    }
+
+    /// Drops revocations and tracked tokens that have expired.
+    pub fn prune(&self, now: i64) {
+        let state = &mut *self.state.write().unwrap();
+        state.revoked.retain(|_, expires_at| *expires_at > now);
+        for live in [&mut state.issued, &mut state.families] {
+            live.retain(|_, tokens| {
+                tokens.retain(|_, expires_at| *expires_at > now);
+                !tokens.is_empty()
+            });
+        }
+    }
+}
+
+impl State {
+    fn revoke_live(&mut self, tokens: HashMap<String, i64>, now: i64) -> usize {
+        let live: Vec<_> = tokens.into_iter().filter(|(_, expires_at)| *expires_at > now).collect();
+        let count = live.len();
+        self.revoked.extend(live);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revokes_a_single_token() {
+        let list = RevocationList::default();
+        list.track("user-1", "family-1", "jti-1", 100);
+        list.track("user-1", "family-1", "jti-2", 100);
+
+        list.revoke("user-1", "jti-1", 100);
+        assert!(list.is_revoked("jti-1"));
+        assert!(!list.is_revoked("jti-2"));
+    }
+
+    #[test]
+    fn revokes_every_live_token_of_a_user() {
+        let list = RevocationList::default();
+        list.track("user-1", "family-1", "jti-1", 100);
+        list.track("user-1", "family-2", "jti-2", 100);
+        list.track("user-1", "family-2", "expired", 10);
+        list.track("user-2", "family-3", "jti-3", 100);
+
+        assert_eq!(list.revoke_user("user-1", 50), 2);
+        assert!(list.is_revoked("jti-1"));
+        assert!(list.is_revoked("jti-2"));
+        assert!(!list.is_revoked("expired"));
+        assert!(!list.is_revoked("jti-3"));
+    }
+
+    #[test]
+    fn revokes_every_live_token_of_a_family() {
+        let list = RevocationList::default();
+        list.track("user-1", "family-1", "jti-1", 100);
+        list.track("user-1", "family-2", "jti-2", 100);
+
+        assert_eq!(list.revoke_family("family-1", 50), 1);
+        assert!(list.is_revoked("jti-1"));
+        assert!(!list.is_revoked("jti-2"));
+    }
+
+    #[test]
+    fn pruning_drops_expired_entries_and_keeps_live_ones() {
+        let list = RevocationList::default();
+        list.revoke("user-1", "expired", 10);
+        list.revoke("user-1", "live", 100);
+        list.track("user-2", "family-1", "expired-issued", 10);
+        list.track("user-2", "family-1", "live-issued", 100);
+        list.track("user-3", "family-2", "gone", 10);
+
+        list.prune(50);
+
+        assert!(!list.is_revoked("expired"));
+        assert!(list.is_revoked("live"));
+
+        let state = list.state.read().unwrap();
+        assert_eq!(state.issued.len(), 1);
+        assert!(state.issued["user-2"].contains_key("live-issued"));
+        assert!(!state.issued["user-2"].contains_key("expired-issued"));
+        assert_eq!(state.families.len(), 1);
+        assert!(!state.families.contains_key("family-2"));
+    }
+}
@@ -0,0 +1,218 @@
+use super::jwt::{Claims, JwtError, JwtKeys};
+use super::refresh::{RefreshTokens, Rotation};
+use super::revocation::RevocationList;
+use crate::storage::store::UserStore;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::Status;
+
+#[derive(Debug, Clone)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: i64,
+}
+
+/// Issues signed access tokens and keeps the opaque refresh tokens that can renew them.
+pub struct SessionManager {
+    jwt: JwtKeys,
+    refresh: RefreshTokens,
+    revoked: RevocationList,
+}
+
+impl SessionManager {
+    pub fn new(jwt: JwtKeys) -> Self {
+        Self {
+            jwt,
+            refresh: RefreshTokens::default(),
+            revoked: RevocationList::default(),
+        }
+    }
+
+    /// Signs an access token and opens a new refresh token family for it.
+    pub fn issue(&self, user_id: &str, roles: &[String]) -> Result<TokenPair, JwtError> {
+        let (family_id, refresh_token) = self.refresh.start_family(user_id);
+        self.issue_with(user_id, roles, &family_id, refresh_token)
+    }
+
+    fn issue_with(
+        &self,
+        user_id: &str,
+        roles: &[String],
+        family_id: &str,
+        refresh_token: String,
+    ) -> Result<TokenPair, JwtError> {
+        let now = now();
+        let claims = Claims {
+            sub: user_id.to_string(),
+            roles: roles.to_vec(),
+            exp: now + self.jwt.access_token_ttl().as_secs() as i64,
+            iat: now,
+            jti: uuid::Uuid::new_v4().to_string(),
+        };
+        let access_token = self.jwt.sign(&claims)?;
+        self.revoked.track(&claims.sub, family_id, &claims.jti, claims.exp);
+
+        Ok(TokenPair {
+            access_token,
+            refresh_token,
+            expires_at: claims.exp,
+        })
+    }
+
+    /// Returns the verified claims of a live, unrevoked access token.
+    pub fn validate(&self, access_token: &str) -> Option<Claims> {
+        let claims = match self.jwt.verify(access_token) {
+            Ok(claims) => claims,
+            Err(e) => {
+                log::debug!("Rejected access token: {}", e);
+                return None;
+            }
+        };
+
+        if self.revoked.is_revoked(&claims.jti) {
+            return None;
+        }
+        Some(claims)
+    }
+
+    /// Exchanges a refresh token for a new pair, rotating it within its family.
+    /// Roles are read from the user store again, so a role change reaches a session at its next
+    /// refresh; roles granted only through `GenerateTokens` do not survive one.
+    /// Presenting a token that was already rotated revokes the whole family, along with every
+    /// access token issued from it.
+    pub async fn refresh(&self, refresh_token: &str, users: &dyn UserStore) -> Result<Option<TokenPair>, Status> {
+        match self.refresh.rotate(refresh_token) {
+            Rotation::Rotated {
+                family_id,
+                refresh_token,
+                user_id,
+            } => {
+                let Some(user) = users.find_by_id(&user_id).await? else {
+                    return Ok(None);
+                };
+                Ok(Some(self.issue_with(&user.id, &user.roles, &family_id, refresh_token)?))
+            }
+            Rotation::Reused { family_id, user_id } => {
+                let tokens = self.revoked.revoke_family(&family_id, now());
+                log::warn!(
+                    "Refresh token reuse for user {}, revoked token family {} and {} access tokens",
+                    user_id,
+                    family_id,
+                    tokens
+                );
+                Ok(None)
+            }
+            Rotation::Invalid => Ok(None),
+        }
+    }
+
+    /// Revokes a single access token until it would have expired anyway.
+    pub fn revoke(&self, claims: &Claims) {
+        self.revoked.revoke(&claims.sub, &claims.jti, claims.exp);
+    }
+
+    /// Drops expired refresh tokens and revocations, so lookups stay cheap without pruning on
+    /// every request.
+    pub fn prune(&self) {
+        let now = now();
+        self.refresh.prune(now);
+        self.revoked.prune(now);
+    }
+
+    /// Prunes every `period` for as long as the manager is alive.
+    pub fn spawn_pruner(self: &Arc<Self>, period: Duration) {
+        let sessions = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match sessions.upgrade() {
+                    Some(sessions) => sessions.prune(),
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// Logs the user out everywhere: every live access token and refresh token family is revoked.
+    pub fn revoke_all(&self, user_id: &str) {
+        let tokens = self.revoked.revoke_user(user_id, now());
+        let families = self.refresh.revoke_user(user_id);
+        log::info!(
+            "Revoked {} access tokens and {} refresh token families for user {}",
+            tokens,
+            families,
+            user_id
+        );
+    }
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryUserStore;
+    use crate::storage::store::User;
+
+    async fn store_with(user_id: &str, roles: &[&str]) -> MemoryUserStore {
+        let store = MemoryUserStore::default();
+        store
+            .create_user(User {
+                id: user_id.to_string(),
+                username: user_id.to_string(),
+                email: format!("{}@example.com", user_id),
+                password_hash: String::new(),
+                roles: roles.iter().map(|role| role.to_string()).collect(),
+                created_at: 0,
+            })
+            .await
+            .unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn refresh_token_reuse_revokes_access_tokens_of_the_family() {
+        let users = store_with("user-1", &["user"]).await;
+        let sessions = SessionManager::new(JwtKeys::ephemeral());
+        let roles = vec!["user".to_string()];
+        let login = sessions.issue("user-1", &roles).unwrap();
+        let other_login = sessions.issue("user-1", &roles).unwrap();
+        let refreshed = sessions.refresh(&login.refresh_token, &users).await.unwrap().unwrap();
+        assert!(sessions.validate(&refreshed.access_token).is_some());
+
+        assert!(sessions.refresh(&login.refresh_token, &users).await.unwrap().is_none());
+
+        assert!(sessions.validate(&login.access_token).is_none());
+        assert!(sessions.validate(&refreshed.access_token).is_none());
+        assert!(sessions.refresh(&refreshed.refresh_token, &users).await.unwrap().is_none());
+        assert!(sessions.validate(&other_login.access_token).is_some());
+    }
+
+    #[tokio::test]
+    async fn refresh_picks_up_the_current_roles() {
+        let users = store_with("user-1", &["user", "admin"]).await;
+        let sessions = SessionManager::new(JwtKeys::ephemeral());
+        let login = sessions.issue("user-1", &["user".to_string()]).unwrap();
+
+        let refreshed = sessions.refresh(&login.refresh_token, &users).await.unwrap().unwrap();
+        let claims = sessions.validate(&refreshed.access_token).unwrap();
+        assert_eq!(claims.roles, vec!["user".to_string(), "admin".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn refresh_fails_once_the_user_is_gone() {
+        let users = MemoryUserStore::default();
+        let sessions = SessionManager::new(JwtKeys::ephemeral());
+        let login = sessions.issue("user-1", &[]).unwrap();
+
+        assert!(sessions.refresh(&login.refresh_token, &users).await.unwrap().is_none());
+    }
+}
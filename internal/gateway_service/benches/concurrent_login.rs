@@ -0,0 +1,119 @@
+//! Compares login throughput of a `GatewayServer` guarded by a global lock with the lock-free
+//! shared client, against an in-process `AuthService` that answers after a fixed delay.
+//!
+//! Run with `cargo bench -p gateway_service --bench concurrent_login`.
+
+use futures::future::join_all;
+use gateway_service::auth::auth_service_server::{AuthService, AuthServiceServer};
+use gateway_service::auth::*;
+use gateway_service::server::backoff::ConnectPolicy;
+use gateway_service::server::service::GatewayServer;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+const UPSTREAM_LATENCY: Duration = Duration::from_millis(5);
+const LOCKED_REQUESTS: usize = 200;
+const CONCURRENT_REQUESTS: usize = 5_000;
+
+struct SlowAuthService;
+
+#[tonic::async_trait]
+impl AuthService for SlowAuthService {
+    async fn login(&self, request: Request<LoginRequest>) -> Result<Response<LoginResponse>, Status> {
+        tokio::time::sleep(UPSTREAM_LATENCY).await;
+        Ok(Response::new(LoginResponse {
+            access_token: request.into_inner().username,
+            refresh_token: String::new(),
+            expires_at: 0,
+        }))
+    }
+
+    async fn refresh(&self, _: Request<RefreshRequest>) -> Result<Response<RefreshResponse>, Status> {
+        Err(Status::unimplemented("refresh"))
+    }
+
+    async fn validate(&self, _: Request<ValidateRequest>) -> Result<Response<ValidateResponse>, Status> {
+        Err(Status::unimplemented("validate"))
+    }
+
+    async fn logout(&self, _: Request<LogoutRequest>) -> Result<Response<LogoutResponse>, Status> {
+        Err(Status::unimplemented("logout"))
+    }
+
+    async fn generate_tokens(
+        &self,
+        _: Request<GenerateTokensRequest>,
+    ) -> Result<Response<GenerateTokensResponse>, Status> {
+        Err(Status::unimplemented("generate_tokens"))
+    }
+
+    async fn register(&self, _: Request<RegisterRequest>) -> Result<Response<RegisterResponse>, Status> {
+        Err(Status::unimplemented("register"))
+    }
+}
+
+async fn spawn_upstream() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(
+        Server::builder()
+            .add_service(AuthServiceServer::new(SlowAuthService))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    addr
+}
+
+fn login_request(i: usize) -> LoginRequest {
+    LoginRequest {
+        username: format!("user{}", i),
+        password: "password".to_string(),
+    }
+}
+
+fn report(name: &str, requests: usize, elapsed: Duration) {
+    println!(
+        "{:<12} {:>6} logins in {:>8.2?} ({:>10.0} req/s)",
+        name,
+        requests,
+        elapsed,
+        requests as f64 / elapsed.as_secs_f64()
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let addr = spawn_upstream().await;
+    let gateway = GatewayServer::new(
+        format!("http://{}", addr),
+        format!("http://{}", addr),
+        None,
+        &ConnectPolicy::default(),
+    )
+    .unwrap();
+
+    // The previous model: one lock held for the whole upstream round-trip
+    let locked = Arc::new(tokio::sync::Mutex::new(gateway.clone()));
+    let start = Instant::now();
+    join_all((0..LOCKED_REQUESTS).map(|i| {
+        let locked = locked.clone();
+        tokio::spawn(async move { locked.lock().await.login(login_request(i)).await.unwrap() })
+    }))
+    .await;
+    report("mutex", LOCKED_REQUESTS, start.elapsed());
+
+    let shared = Arc::new(gateway);
+    let start = Instant::now();
+    join_all((0..CONCURRENT_REQUESTS).map(|i| {
+        let shared = shared.clone();
+        tokio::spawn(async move { shared.login(login_request(i)).await.unwrap() })
+    }))
+    .await;
+    report("shared", CONCURRENT_REQUESTS, start.elapsed());
+}
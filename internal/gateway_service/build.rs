@@ -2,8 +2,12 @@ use std::env;
 use std::path::PathBuf;
 
 fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
     tonic_build::configure()
-        .out_dir(PathBuf::from(env::var("OUT_DIR").unwrap()))
-        .compile(&["../../proto/auth_service.proto"], &["../../proto"])
+        .out_dir(&out_dir)
+        // Descriptors for JSON transcoding, embedded into the binary
+        .file_descriptor_set_path(out_dir.join("gateway_descriptor.bin"))
+        .compile_protos(&["../../proto/auth_service.proto", "../../proto/hello.proto"], &["../../proto"])
         .expect("Failed to compile protos");
 }
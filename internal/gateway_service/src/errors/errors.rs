@@ -1,17 +1,212 @@
+use bytes::Bytes;
+use hyper::{Response, StatusCode};
 use quinn::ConfigError;
+use serde::Serialize;
 use thiserror::Error;
-use tonic::{transport, Status};
+use tonic::{transport, Code, Status};
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum GatewayError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
-    #[error("gRPC transport error: {0}")]
+    #[error("gRPC transport error: {}", transport_reason(.0))]
     TransportError(#[from] transport::Error),
 
     #[error("gRPC status error: {0}")]
-    StatusError(#[from] Status),
+    StatusError(Box<Status>),
+
+    #[error("Invalid request: {0}")]
+    InvalidRequestError(String),
+
+    #[error("Request body exceeds {0} bytes")]
+    PayloadTooLargeError(usize),
+
+    #[error("No route for {0}")]
+    RouteNotFoundError(String),
+
+    #[error("Method {0} not allowed")]
+    MethodNotAllowedError(String),
+
+    #[error("Unauthenticated: {0}")]
+    UnauthenticatedError(String),
+
+    #[error("Permission denied: {0}")]
+    PermissionDeniedError(String),
+
+    #[error("Internal error: {0}")]
+    InternalError(String),
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorEnvelope<'a> {
+    error: ErrorBody<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody<'a> {
+    code: &'static str,
+    message: String,
+    request_id: &'a str,
+}
+
+impl GatewayError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            GatewayError::StatusError(status) => http_status_for(status.code()),
+            GatewayError::TransportError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            GatewayError::InvalidRequestError(_) => StatusCode::BAD_REQUEST,
+            GatewayError::PayloadTooLargeError(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            GatewayError::RouteNotFoundError(_) => StatusCode::NOT_FOUND,
+            GatewayError::MethodNotAllowedError(_) => StatusCode::METHOD_NOT_ALLOWED,
+            GatewayError::UnauthenticatedError(_) => StatusCode::UNAUTHORIZED,
+            GatewayError::PermissionDeniedError(_) => StatusCode::FORBIDDEN,
+            GatewayError::ConfigError(_) | GatewayError::InternalError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Machine-readable error code. Upstream failures keep their canonical gRPC code name.
+    pub fn code(&self) -> &'static str {
+        match self {
+            GatewayError::StatusError(status) => grpc_code_name(status.code()),
+            GatewayError::TransportError(_) => "UNAVAILABLE",
+            GatewayError::InvalidRequestError(_) => "INVALID_ARGUMENT",
+            GatewayError::PayloadTooLargeError(_) => "PAYLOAD_TOO_LARGE",
+            GatewayError::RouteNotFoundError(_) => "NOT_FOUND",
+            GatewayError::MethodNotAllowedError(_) => "METHOD_NOT_ALLOWED",
+            GatewayError::UnauthenticatedError(_) => "UNAUTHENTICATED",
+            GatewayError::PermissionDeniedError(_) => "PERMISSION_DENIED",
+            GatewayError::ConfigError(_) | GatewayError::InternalError(_) => "INTERNAL",
+        }
+    }
+
+    /// Client-facing message. Server-side failures are logged with their details by the
+    /// caller and answered with a generic message, so upstream internals do not leak.
+    fn message(&self) -> String {
+        match self {
+            GatewayError::StatusError(status) if !http_status_for(status.code()).is_server_error() => {
+                status.message().to_string()
+            }
+            GatewayError::InvalidRequestError(_)
+            | GatewayError::PayloadTooLargeError(_)
+            | GatewayError::RouteNotFoundError(_)
+            | GatewayError::MethodNotAllowedError(_)
+            | GatewayError::UnauthenticatedError(_)
+            | GatewayError::PermissionDeniedError(_) => self.to_string(),
+            _ => self
+                .status_code()
+                .canonical_reason()
+                .unwrap_or("Internal Server Error")
+                .to_string(),
+        }
+    }
+
+    /// Renders the error as the JSON envelope returned to HTTP clients.
+    pub fn to_response(&self, request_id: &str) -> Response<Bytes> {
+        let envelope = ErrorEnvelope {
+            error: ErrorBody {
+                code: self.code(),
+                message: self.message(),
+                request_id,
+            },
+        };
+        let body = serde_json::to_vec(&envelope).unwrap_or_default();
+
+        let mut builder = Response::builder()
+            .status(self.status_code())
+            .header("content-type", "application/json");
+        if let GatewayError::UnauthenticatedError(_) = self {
+            builder = builder.header("www-authenticate", "Bearer");
+        }
+
+        builder
+            .body(Bytes::from(body))
+            .unwrap_or_else(|_| {
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Bytes::from_static(b"Internal Server Error"))
+                    .unwrap()
+            })
+    }
+}
+
+impl GatewayError {
+    /// The error as a gRPC status, for callers that speak gRPC rather than JSON.
+    pub fn to_status(&self) -> Status {
+        match self {
+            GatewayError::StatusError(status) => Status::new(status.code(), status.message()),
+            GatewayError::TransportError(_) => Status::unavailable(self.message()),
+            GatewayError::InvalidRequestError(_) => Status::invalid_argument(self.to_string()),
+            GatewayError::PayloadTooLargeError(_) => Status::resource_exhausted(self.to_string()),
+            GatewayError::RouteNotFoundError(_) | GatewayError::MethodNotAllowedError(_) => {
+                Status::unimplemented(self.to_string())
+            }
+            GatewayError::UnauthenticatedError(_) => Status::unauthenticated(self.to_string()),
+            GatewayError::PermissionDeniedError(_) => Status::permission_denied(self.to_string()),
+            GatewayError::ConfigError(_) | GatewayError::InternalError(_) => Status::internal(self.message()),
+        }
+    }
+}
+
+/// tonic's transport error only says "transport error"; the cause, such as a refused
+/// connection, is further down the source chain.
+fn transport_reason(error: &transport::Error) -> String {
+    let mut reason: &dyn std::error::Error = error;
+    while let Some(source) = reason.source() {
+        reason = source;
+    }
+    reason.to_string()
+}
+
+fn http_status_for(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::Cancelled | Code::Unknown | Code::Internal | Code::DataLoss => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+fn grpc_code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "OK",
+        Code::Cancelled => "CANCELLED",
+        Code::Unknown => "UNKNOWN",
+        Code::InvalidArgument => "INVALID_ARGUMENT",
+        Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+        Code::NotFound => "NOT_FOUND",
+        Code::AlreadyExists => "ALREADY_EXISTS",
+        Code::PermissionDenied => "PERMISSION_DENIED",
+        Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+        Code::FailedPrecondition => "FAILED_PRECONDITION",
+        Code::Aborted => "ABORTED",
+        Code::OutOfRange => "OUT_OF_RANGE",
+        Code::Unimplemented => "UNIMPLEMENTED",
+        Code::Internal => "INTERNAL",
+        Code::Unavailable => "UNAVAILABLE",
+        Code::DataLoss => "DATA_LOSS",
+        Code::Unauthenticated => "UNAUTHENTICATED",
+    }
+}
+
+impl From<Status> for GatewayError {
+    fn from(status: Status) -> Self {
+        GatewayError::StatusError(Box::new(status))
+    }
 }
 
 impl From<ConfigError> for GatewayError {
@@ -19,3 +214,85 @@ impl From<ConfigError> for GatewayError {
         GatewayError::ConfigError(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(error: &GatewayError) -> serde_json::Value {
+        serde_json::from_slice(error.to_response("req-1").body()).unwrap()
+    }
+
+    #[test]
+    fn maps_upstream_codes_to_http_statuses() {
+        let cases = [
+            (Code::Unauthenticated, StatusCode::UNAUTHORIZED, "UNAUTHENTICATED"),
+            (Code::PermissionDenied, StatusCode::FORBIDDEN, "PERMISSION_DENIED"),
+            (Code::InvalidArgument, StatusCode::BAD_REQUEST, "INVALID_ARGUMENT"),
+            (Code::NotFound, StatusCode::NOT_FOUND, "NOT_FOUND"),
+            (Code::AlreadyExists, StatusCode::CONFLICT, "ALREADY_EXISTS"),
+            (Code::Unavailable, StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE"),
+            (Code::DeadlineExceeded, StatusCode::GATEWAY_TIMEOUT, "DEADLINE_EXCEEDED"),
+            (Code::Internal, StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+        ];
+
+        for (code, status, name) in cases {
+            let error = GatewayError::from(Status::new(code, "upstream says no"));
+            let response = error.to_response("req-1");
+            assert_eq!(response.status(), status, "{:?}", code);
+            assert_eq!(response.headers()["content-type"], "application/json");
+
+            let body = body(&error);
+            assert_eq!(body["error"]["code"], name, "{:?}", code);
+            assert_eq!(body["error"]["request_id"], "req-1");
+        }
+    }
+
+    #[test]
+    fn maps_gateway_errors_to_http_statuses() {
+        let cases = [
+            (GatewayError::InvalidRequestError("bad".into()), StatusCode::BAD_REQUEST, "INVALID_ARGUMENT"),
+            (GatewayError::PayloadTooLargeError(1024), StatusCode::PAYLOAD_TOO_LARGE, "PAYLOAD_TOO_LARGE"),
+            (GatewayError::RouteNotFoundError("/x".into()), StatusCode::NOT_FOUND, "NOT_FOUND"),
+            (GatewayError::MethodNotAllowedError("GET".into()), StatusCode::METHOD_NOT_ALLOWED, "METHOD_NOT_ALLOWED"),
+            (GatewayError::UnauthenticatedError("no token".into()), StatusCode::UNAUTHORIZED, "UNAUTHENTICATED"),
+            (GatewayError::PermissionDeniedError("no role".into()), StatusCode::FORBIDDEN, "PERMISSION_DENIED"),
+            (GatewayError::ConfigError("bad".into()), StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+            (GatewayError::InternalError("bad".into()), StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+        ];
+
+        for (error, status, name) in cases {
+            assert_eq!(error.to_response("req-1").status(), status, "{}", error);
+            assert_eq!(body(&error)["error"]["code"], name, "{}", error);
+        }
+    }
+
+    #[test]
+    fn unauthenticated_asks_for_a_bearer_token() {
+        let response = GatewayError::UnauthenticatedError("no token".into()).to_response("req-1");
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    }
+
+    #[test]
+    fn client_errors_keep_their_message() {
+        let error = GatewayError::from(Status::invalid_argument("username is required"));
+        assert_eq!(body(&error)["error"]["message"], "username is required");
+    }
+
+    #[test]
+    fn server_errors_do_not_leak_details() {
+        let errors = [
+            GatewayError::InternalError("failed to encode /secret/path".into()),
+            GatewayError::ConfigError("invalid descriptor set /etc/gateway/api.pb".into()),
+            GatewayError::from(Status::internal("sqlite: no such table users")),
+            GatewayError::from(Status::unavailable("dial tcp 10.0.0.7:50051")),
+        ];
+
+        for error in errors {
+            let message = body(&error)["error"]["message"].as_str().unwrap().to_string();
+            for detail in ["/secret/path", "api.pb", "sqlite", "10.0.0.7"] {
+                assert!(!message.contains(detail), "{} leaked into '{}'", detail, message);
+            }
+        }
+    }
+}
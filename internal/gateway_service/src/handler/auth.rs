@@ -1,148 +0,0 @@
-use tonic::{Request, Response, Status};
-
-#[derive(Debug, Default)]
-pub struct AuthHandler;
-
-pub struct LoginRequest {
-    pub username: String,
-    pub password: String,
-}
-
-pub struct LoginResponse {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub expires_at: i64,
-}
-
-pub struct RefreshRequest {
-    pub refresh_token: String,
-}
-
-pub struct RefreshResponse {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub expires_at: i64,
-}
-
-// Define other request/response structs as needed
-pub struct GenerateTokensRequest {
-    pub refresh_token: String,
-}
-
-pub struct GenerateTokensResponse {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub expires_at: i64,
-}
-
-pub struct ValidateRequest {
-    pub token: String,
-}
-
-pub struct ValidateResponse {
-    pub valid: bool,
-    pub user_id: String,
-    pub roles: Vec<String>,
-    pub expires_at: i64,
-}
-
-pub struct LogoutRequest {
-    pub token: String,
-}
-
-pub struct LogoutResponse {
-    pub success: bool,
-    pub message: String,
-}
-
-pub struct RegisterRequest {
-    pub username: String,
-    pub password: String,
-}
-
-pub struct RegisterResponse {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub expires_at: i64,
-}
-
-#[tonic::async_trait]
-impl AuthService for AuthHandler {
-    async fn login(
-        &self,
-        request: Request<LoginRequest>,
-    ) -> Result<Response<LoginResponse>, Status> {
-        let req = request.into_inner();
-
-        Ok(Response::new(LoginResponse {
-            access_token: "example_access_token".to_string(),
-            refresh_token: "example_refresh_token".to_string(),
-            expires_at: 1893456000,
-        }))
-    }
-
-    async fn refresh(
-        &self,
-        request: Request<RefreshRequest>,
-    ) -> Result<Response<RefreshResponse>, Status> {
-        let req = request.into_inner();
-
-        Ok(Response::new(RefreshResponse {
-            access_token: "example_new_access_token".to_string(),
-            refresh_token: "example_new_refresh_token".to_string(),
-            expires_at: 1893456000,
-        }))
-    }
-
-    async fn validate(
-        &self,
-        request: Request<ValidateRequest>,
-    ) -> Result<Response<ValidateResponse>, Status> {
-        let req = request.into_inner();
-
-        Ok(Response::new(ValidateResponse {
-            valid: true,
-            user_id: "example_user_id".to_string(),
-            roles: vec!["user".to_string()],
-            expires_at: 1893456000,
-        }))
-    }
-
-    async fn logout(
-        &self,
-        request: Request<LogoutRequest>,
-    ) -> Result<Response<LogoutResponse>, Status> {
-        let req = request.into_inner();
-
-        Ok(Response::new(LogoutResponse {
-            success: true,
-            message: "Successfully logged out".to_string(),
-        }))
-    }
-
-    async fn register(
-        &self,
-        request: Request<RegisterRequest>,
-    ) -> Result<Response<RegisterResponse>, Status> {
-        let req = request.into_inner();
-
-        Ok(Response::new(RegisterResponse {
-            access_token: "example_registration_token".to_string(),
-            refresh_token: "example_registration_refresh_token".to_string(),
-            expires_at: 1893456000,
-        }))
-    }
-
-    async fn generate_tokens(
-        &self,
-        request: Request<GenerateTokensRequest>,
-    ) -> Result<Response<GenerateTokensResponse>, Status> {
-        let req = request.into_inner();
-
-        Ok(Response::new(GenerateTokensResponse {
-            access_token: "example_generated_access_token".to_string(),
-            refresh_token: "example_generated_refresh_token".to_string(),
-            expires_at: 1893456000,
-        }))
-    }
-}
@@ -0,0 +1,332 @@
+use crate::errors::errors::GatewayError;
+use crate::router::router::{json_response, HandlerResult};
+use crate::server::service::Upstream;
+use bytes::Bytes;
+use hyper::{Method, Response, StatusCode};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+
+pub const LIVENESS_PATH: &str = "/healthz";
+pub const READINESS_PATH: &str = "/readyz";
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamState {
+    /// Not checked yet.
+    Unknown,
+    Serving,
+    NotServing,
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamStatus {
+    pub name: String,
+    pub address: String,
+    /// Whether the gateway is only ready while this upstream is serving.
+    pub required: bool,
+    pub status: UpstreamState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    /// Set once shutdown has begun; the gateway stays not ready until it exits.
+    pub draining: bool,
+    pub upstreams: Vec<UpstreamStatus>,
+}
+
+struct Target {
+    upstream: Upstream,
+    status: RwLock<UpstreamStatus>,
+}
+
+/// Polls `grpc.health.v1.Health/Check` on every upstream through the channels the gateway
+/// itself uses, and answers the liveness and readiness probes from the last results.
+pub struct HealthMonitor {
+    targets: Vec<Target>,
+    interval: Duration,
+    timeout: Duration,
+    draining: AtomicBool,
+}
+
+impl HealthMonitor {
+    /// Upstreams are checked once per address; the first entry for an address wins, so list
+    /// the required ones first.
+    pub fn new(upstreams: Vec<(Upstream, bool)>, config: Option<&config::HealthCheckConfig>) -> Self {
+        let mut targets: Vec<Target> = Vec::new();
+        for (upstream, required) in upstreams {
+            if targets.iter().any(|target| target.upstream.address == upstream.address) {
+                continue;
+            }
+            let status = UpstreamStatus {
+                name: upstream.name.clone(),
+                address: upstream.address.clone(),
+                required,
+                status: UpstreamState::Unknown,
+                error: None,
+            };
+            targets.push(Target {
+                upstream,
+                status: RwLock::new(status),
+            });
+        }
+
+        Self {
+            targets,
+            interval: config
+                .and_then(|config| config.interval_secs)
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_INTERVAL),
+            timeout: config
+                .and_then(|config| config.timeout_secs)
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_TIMEOUT),
+            draining: AtomicBool::new(false),
+        }
+    }
+
+    /// Fails the readiness probe from now on, so load balancers stop sending new requests
+    /// before the listeners close.
+    pub fn set_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks every upstream right away and then every `interval`.
+    pub fn spawn(self: &Arc<Self>) {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(monitor.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                monitor.check_all().await;
+            }
+        });
+    }
+
+    async fn check_all(&self) {
+        futures::future::join_all(self.targets.iter().map(|target| self.check(target))).await;
+    }
+
+    async fn check(&self, target: &Target) {
+        if !target.upstream.connector.probe().await {
+            let error = "still refuses connections, backing off".to_string();
+            return self.record(target, UpstreamState::Unreachable, Some(error));
+        }
+
+        let mut client = HealthClient::new(target.upstream.channel.clone());
+        let (state, error) = match tokio::time::timeout(self.timeout, client.check(HealthCheckRequest::default())).await {
+            Ok(Ok(response)) => match response.into_inner().status() {
+                ServingStatus::Serving => (UpstreamState::Serving, None),
+                status => (UpstreamState::NotServing, Some(format!("reported {}", status.as_str_name()))),
+            },
+            Ok(Err(status)) if status.code() == tonic::Code::Unavailable => {
+                (UpstreamState::Unreachable, Some(status.message().to_string()))
+            }
+            Ok(Err(status)) => (UpstreamState::NotServing, Some(format!("{:?}: {}", status.code(), status.message()))),
+            Err(_) => (UpstreamState::Unreachable, Some(format!("no answer within {:?}", self.timeout))),
+        };
+        self.record(target, state, error);
+    }
+
+    fn record(&self, target: &Target, state: UpstreamState, error: Option<String>) {
+        let mut status = target.status.write().unwrap();
+        if status.status != state {
+            match state {
+                UpstreamState::Serving => log::info!("Upstream {} is serving", status.name),
+                _ => log::warn!(
+                    "Upstream {} is {:?}: {}",
+                    status.name,
+                    state,
+                    error.as_deref().unwrap_or_default()
+                ),
+            }
+        }
+        status.status = state;
+        status.error = error;
+    }
+
+    /// Ready once every required upstream has passed its latest check, until draining starts.
+    pub fn readiness(&self) -> Readiness {
+        let upstreams: Vec<UpstreamStatus> = self
+            .targets
+            .iter()
+            .map(|target| target.status.read().unwrap().clone())
+            .collect();
+        let draining = self.draining.load(Ordering::Relaxed);
+        Readiness {
+            ready: !draining
+                && upstreams
+                    .iter()
+                    .all(|upstream| !upstream.required || upstream.status == UpstreamState::Serving),
+            draining,
+            upstreams,
+        }
+    }
+
+    /// Answers the liveness and readiness probes, or `None` for any other path.
+    pub fn handle(&self, method: &Method, path: &str) -> Option<HandlerResult<Response<Bytes>>> {
+        if path != LIVENESS_PATH && path != READINESS_PATH {
+            return None;
+        }
+        if method != Method::GET {
+            return Some(Err(GatewayError::MethodNotAllowedError(method.to_string())));
+        }
+
+        if path == LIVENESS_PATH {
+            return Some(json_response(&serde_json::json!({ "status": "ok" })));
+        }
+        let readiness = self.readiness();
+        Some(json_response(&readiness).map(|mut response| {
+            if !readiness.ready {
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            }
+            response
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::backoff::ConnectPolicy;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    fn readiness_status(monitor: &HealthMonitor) -> StatusCode {
+        monitor.handle(&Method::GET, READINESS_PATH).unwrap().unwrap().status()
+    }
+
+    fn liveness_status(monitor: &HealthMonitor) -> StatusCode {
+        monitor.handle(&Method::GET, LIVENESS_PATH).unwrap().unwrap().status()
+    }
+
+    fn upstream(name: &str, address: String) -> Upstream {
+        Upstream::connect(name.to_string(), address, None, &ConnectPolicy::default()).unwrap()
+    }
+
+    fn serve_health(listener: TcpListener) {
+        let (_, health) = tonic_health::server::health_reporter();
+        tokio::spawn(
+            Server::builder()
+                .add_service(health)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+    }
+
+    /// An in-process upstream whose health service reports SERVING.
+    async fn serving_upstream(name: &str) -> Upstream {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        serve_health(listener);
+        upstream(name, address)
+    }
+
+    fn unreachable_upstream(name: &str) -> Upstream {
+        upstream(name, "http://127.0.0.1:1".to_string())
+    }
+
+    fn states(monitor: &HealthMonitor) -> Vec<UpstreamState> {
+        monitor.readiness().upstreams.iter().map(|upstream| upstream.status).collect()
+    }
+
+    #[tokio::test]
+    async fn unchecked_upstreams_are_not_ready() {
+        let monitor = HealthMonitor::new(vec![(serving_upstream("auth").await, true)], None);
+        assert_eq!(states(&monitor), vec![UpstreamState::Unknown]);
+        assert_eq!(readiness_status(&monitor), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn required_upstream_down_fails_readiness() {
+        let monitor = HealthMonitor::new(
+            vec![(unreachable_upstream("auth"), true), (serving_upstream("hello").await, false)],
+            None,
+        );
+        monitor.check_all().await;
+
+        assert_eq!(states(&monitor), vec![UpstreamState::Unreachable, UpstreamState::Serving]);
+        assert_eq!(readiness_status(&monitor), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(liveness_status(&monitor), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn optional_upstream_down_keeps_readiness() {
+        let monitor = HealthMonitor::new(
+            vec![(serving_upstream("auth").await, true), (unreachable_upstream("hello"), false)],
+            None,
+        );
+        monitor.check_all().await;
+
+        assert_eq!(states(&monitor), vec![UpstreamState::Serving, UpstreamState::Unreachable]);
+        assert_eq!(readiness_status(&monitor), StatusCode::OK);
+        assert_eq!(liveness_status(&monitor), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn not_serving_upstream_fails_readiness() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let (mut reporter, health) = tonic_health::server::health_reporter();
+        reporter.set_service_status("", tonic_health::ServingStatus::NotServing).await;
+        tokio::spawn(
+            Server::builder()
+                .add_service(health)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let monitor = HealthMonitor::new(vec![(upstream("auth", address), true)], None);
+        monitor.check_all().await;
+
+        assert_eq!(states(&monitor), vec![UpstreamState::NotServing]);
+        assert_eq!(readiness_status(&monitor), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(liveness_status(&monitor), StatusCode::OK);
+    }
+
+    #[test]
+    fn draining_fails_readiness_but_not_liveness() {
+        let monitor = HealthMonitor::new(Vec::new(), None);
+        assert_eq!(readiness_status(&monitor), StatusCode::OK);
+
+        monitor.set_draining();
+        assert!(!monitor.readiness().ready);
+        assert_eq!(readiness_status(&monitor), StatusCode::SERVICE_UNAVAILABLE);
+
+        assert_eq!(liveness_status(&monitor), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn recovered_upstream_serves_without_waiting_out_the_backoff() {
+        // Reserve a free port, then close it so the upstream starts out down
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let socket = listener.local_addr().unwrap();
+        drop(listener);
+
+        let policy = ConnectPolicy {
+            initial_backoff: Duration::from_secs(60),
+            ..ConnectPolicy::default()
+        };
+        let upstream = Upstream::connect("auth".to_string(), format!("http://{}", socket), None, &policy).unwrap();
+        let monitor = HealthMonitor::new(vec![(upstream, true)], None);
+        monitor.check_all().await;
+        monitor.check_all().await;
+        assert_eq!(states(&monitor), vec![UpstreamState::Unreachable]);
+
+        serve_health(TcpListener::bind(socket).await.unwrap());
+        monitor.check_all().await;
+        assert_eq!(states(&monitor), vec![UpstreamState::Serving]);
+        assert_eq!(readiness_status(&monitor), StatusCode::OK);
+    }
+}
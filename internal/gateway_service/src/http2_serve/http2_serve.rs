@@ -1,214 +1,111 @@
-use crate::auth::{LoginRequest, LoginResponse, RefreshRequest, RefreshResponse};
-use crate::server::service::GatewayServer;
+use crate::errors::errors::GatewayError;
+use crate::listener::listener::ListenerSettings;
+use crate::proxy::grpc_proxy::ProxyBody;
+use crate::router::router::{request_id, Router, MAX_BODY_SIZE};
+use crate::server::service::PeerCertificate;
+use crate::shutdown::shutdown::Shutdown;
+use crate::tls::peer::peer_certificate;
 use bytes::Bytes;
 use futures::future::BoxFuture;
-use http_body_util::{combinators::BoxBody, BodyExt, Full};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
 use hyper::{
     body::Incoming as Body,
     server::conn::http2,
     service::Service,
-    Method, Request, Response, StatusCode,
+    Request, Response,
 };
 use hyper_util::rt::{TokioExecutor, TokioIo};
-use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use hyper_util::server::graceful::GracefulShutdown;
 use rustls::ServerConfig;
-use rustls_pemfile::{certs, pkcs8_private_keys};
-use serde::{Deserialize, Serialize};
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tokio::sync::Mutex;
 use tokio_rustls::TlsAcceptor;
 use tracing::error;
 
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct HttpLoginRequest {
-    pub username: String,
-    pub password: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct HttpRefreshRequest {
-    pub refresh_token: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct HttpLoginResponse {
-    pub access_token: String,
-    pub refresh_token: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct HttpRefreshResponse {
-    pub access_token: String,
-}
-
-fn full<T: Into<Bytes>>(chunk: T) -> BoxBody<Bytes, hyper::Error> {
+fn full<T: Into<Bytes>>(chunk: T) -> ProxyBody {
     Full::new(chunk.into())
         .map_err(|never| match never {})
-        .boxed()
-}
-
-fn json_response<T: Serialize>(
-    value: &T,
-) -> Result<Response<BoxBody<Bytes, hyper::Error>>, Box<dyn Error + Send + Sync>> {
-    let json = serde_json::to_vec(value)?;
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header("content-type", "application/json")
-        .body(full(json))?)
+        .boxed_unsync()
 }
 
-fn error_response(
-    status: StatusCode,
-    message: impl Into<String>,
-) -> Response<BoxBody<Bytes, hyper::Error>> {
-    Response::builder()
-        .status(status)
-        .header("content-type", "text/plain")
-        .body(full(message.into()))
-        .unwrap_or_else(|_| {
-            Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(full("Internal Server Error"))
-                .unwrap()
-        })
+fn stream_body(body: Body) -> ProxyBody {
+    body.map_err(|e| tonic::Status::from_error(e.into())).boxed_unsync()
 }
 
 #[derive(Clone)]
 struct GatewayHttpService {
-    gateway: Arc<Mutex<GatewayServer>>,
-}
-
-impl GatewayHttpService {
-    async fn handle_login(
-        &self,
-        req: HttpLoginRequest,
-    ) -> Result<HttpLoginResponse, Box<dyn Error + Send + Sync>> {
-        let mut gateway = self.gateway.lock().await;
-        let grpc_req = LoginRequest {
-            username: req.username,
-            password: req.password,
-        };
-        let grpc_res = gateway.login(grpc_req).await?;
-        Ok(HttpLoginResponse {
-            access_token: grpc_res.access_token,
-            refresh_token: grpc_res.refresh_token,
-        })
-    }
-
-    async fn handle_refresh(
-        &self,
-        req: HttpRefreshRequest,
-    ) -> Result<HttpRefreshResponse, Box<dyn Error + Send + Sync>> {
-        let mut gateway = self.gateway.lock().await;
-        let grpc_req = RefreshRequest {
-            refresh_token: req.refresh_token,
-        };
-        let grpc_res = gateway.refresh(grpc_req).await?;
-        Ok(HttpRefreshResponse {
-            access_token: grpc_res.access_token,
-        })
-    }
+    router: Router,
+    peer: Option<PeerCertificate>,
 }
 
 impl Service<Request<Body>> for GatewayHttpService {
-    type Response = Response<BoxBody<Bytes, hyper::Error>>;
+    type Response = Response<ProxyBody>;
     type Error = hyper::Error;
     type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
-    fn call(&self, req: Request<Body>) -> Self::Future {
+    fn call(&self, mut req: Request<Body>) -> Self::Future {
         let service = self.clone();
+        if let Some(peer) = &self.peer {
+            req.extensions_mut().insert(peer.clone());
+        }
 
         Box::pin(async move {
+            if let Some(proxy) = service.router.grpc_proxy_for(&req) {
+                return Ok(proxy.forward(req.map(stream_body)).await);
+            }
+            if let Some(grpc_web) = service.router.grpc_web_for(&req) {
+                return Ok(grpc_web.handle(req.map(stream_body)).await);
+            }
+
             let (parts, body) = req.into_parts();
-            let body_bytes = match body.collect().await {
+            let body_bytes = match Limited::new(body, MAX_BODY_SIZE).collect().await {
                 Ok(collected) => collected.to_bytes(),
-                Err(e) => return Ok(error_response(StatusCode::BAD_REQUEST, e.to_string())),
-            };
-
-            let response = match (parts.method, parts.uri.path()) {
-                (Method::POST, "/login") => {
-                    match serde_json::from_slice::<HttpLoginRequest>(&body_bytes) {
-                        Ok(parsed_req) => match service.handle_login(parsed_req).await {
-                            Ok(res) => json_response(&res)
-                                .unwrap_or_else(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-                            Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-                        },
-                        Err(e) => error_response(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)),
-                    }
-                }
-                (Method::POST, "/refresh") => {
-                    match serde_json::from_slice::<HttpRefreshRequest>(&body_bytes) {
-                        Ok(parsed_req) => match service.handle_refresh(parsed_req).await {
-                            Ok(res) => json_response(&res)
-                                .unwrap_or_else(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-                            Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-                        },
-                        Err(e) => error_response(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)),
-                    }
+                Err(e) => {
+                    let req = Request::from_parts(parts, ());
+                    let error = if e.is::<LengthLimitError>() {
+                        GatewayError::PayloadTooLargeError(MAX_BODY_SIZE)
+                    } else {
+                        GatewayError::InvalidRequestError(e.to_string())
+                    };
+                    return Ok(error.to_response(&request_id(&req)).map(full));
                 }
-                _ => error_response(StatusCode::NOT_FOUND, "Not Found"),
             };
 
-            Ok(response)
-        })
-    }
-}
-
-
-fn load_tls_config() -> Result<ServerConfig, Box<dyn Error + Send + Sync>> {
-    let cert_file = File::open("cert.pem")
-        .map_err(|e| format!("Failed to open cert.pem: {}", e))?;
-    let key_file = File::open("key.pem")
-        .map_err(|e| format!("Failed to open key.pem: {}", e))?;
-
-    let mut cert_reader = BufReader::new(cert_file);
-    let mut key_reader = BufReader::new(key_file);
-
-    let certs = certs(&mut cert_reader)
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|_| "Failed to parse certificate".to_string())?;
+            let response = service
+                .router
+                .handle(Request::from_parts(parts, body_bytes))
+                .await;
 
-    let mut keys = pkcs8_private_keys(&mut key_reader)
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|_| "Failed to parse private key".to_string())?;
-
-    if keys.is_empty() {
-        return Err("No private keys found".into());
+            Ok(response.map(full))
+        })
     }
-
-    let key = PrivateKeyDer::Pkcs8(keys.remove(0));
-
-    let config = ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(certs, key)
-        .map_err(|e| format!("Failed to create TLS config: {}", e))?;
-
-    Ok(config)
 }
 
 
 pub async fn run_http2_server(
-    addr: SocketAddr,
-    gateway: Arc<Mutex<GatewayServer>>,
+    settings: ListenerSettings,
+    tls_config: ServerConfig,
+    router: Router,
+    shutdown: Shutdown,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let addr = settings.address;
     let listener = TcpListener::bind(addr).await?;
-    let tls_config = tokio::task::spawn_blocking(load_tls_config).await??;
     let acceptor = TlsAcceptor::from(Arc::new(tls_config));
+    let graceful = GracefulShutdown::new();
 
     tracing::info!("HTTP/2 server with TLS listening on {}", addr);
 
     loop {
-        let (stream, _) = listener.accept().await?;
-        let acceptor = acceptor.clone();
-        let service = GatewayHttpService {
-            gateway: gateway.clone(),
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.triggered() => break,
         };
+        let acceptor = acceptor.clone();
+        let router = router.clone();
+        // Held through the handshake too, so a connection accepted just before shutdown is drained
+        let watcher = graceful.watcher();
 
         tokio::spawn(async move {
             let stream = match acceptor.accept(stream).await {
@@ -219,13 +116,28 @@ pub async fn run_http2_server(
                 }
             };
 
+            let service = GatewayHttpService {
+                router,
+                peer: peer_certificate(stream.get_ref().1.peer_certificates()),
+            };
+
             let io = TokioIo::new(stream);
             let conn = http2::Builder::new(TokioExecutor::new())
                 .serve_connection(io, service);
 
-            if let Err(e) = conn.await {
+            if let Err(e) = watcher.watch(conn).await {
                 error!("HTTP/2 connection error: {}", e);
             }
         });
     }
+
+    // Stop accepting, then send GOAWAY on every open connection and let in-flight streams finish
+    drop(listener);
+    if tokio::time::timeout(shutdown.drain_timeout(), graceful.shutdown())
+        .await
+        .is_err()
+    {
+        tracing::warn!("HTTP/2 connections still open after {:?}, closing them", shutdown.drain_timeout());
+    }
+    Ok(())
 }
@@ -1,80 +1,209 @@
-use crate::server::service::GatewayServer;
+use crate::listener::listener::ListenerSettings;
+use crate::proxy::grpc_proxy::ProxyBody;
+use crate::errors::errors::GatewayError;
+use crate::router::router::{request_id, Router, MAX_BODY_SIZE};
+use crate::server::service::PeerCertificate;
+use crate::shutdown::shutdown::Shutdown;
+use crate::tls::peer::peer_certificate;
 use anyhow::Result;
-use quinn::{Connection, Endpoint, ServerConfig};
-use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
-use std::net::SocketAddr;
+use bytes::{Buf, Bytes, BytesMut};
+use h3::server::{RequestResolver, RequestStream};
+use http_body_util::{BodyExt, Full};
+use hyper::{Request, Response};
+use quinn::{Endpoint, Incoming, ServerConfig, VarInt};
+use rustls::pki_types::CertificateDer;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::time::Duration;
+use tokio_util::task::TaskTracker;
+
+// RFC 9114 8.1: graceful close without an error
+const H3_NO_ERROR: VarInt = VarInt::from_u32(0x100);
+const CLOSE_LINGER_MIN: Duration = Duration::from_millis(50);
 
 pub async fn run_http3_server(
-    addr: SocketAddr,
-    gateway: Arc<tokio::sync::Mutex<GatewayServer>>,
+    settings: ListenerSettings,
+    crypto: rustls::ServerConfig,
+    router: Router,
+    shutdown: Shutdown,
 ) -> Result<()> {
-    // Generate a self-signed certificate (for development)
-    let cert_key = rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
-    let cert_chain = vec![CertificateDer::from(cert_key.cert.der().to_vec())];
-    let priv_key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert_key.key_pair.serialized_der().to_vec()));
-
-    // Configure TLS with rustls 0.23.27 for QUIC
-    let mut crypto = rustls::ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(cert_chain, priv_key)?;
-
-    // Configure ALPN for HTTP/3
-    crypto.alpn_protocols = vec![b"h3".to_vec()];
+    let addr = settings.address;
 
     // Create QUIC-compatible server config
     let crypto = quinn::crypto::rustls::QuicServerConfig::try_from(crypto)
         .map_err(|e| anyhow::anyhow!("Failed to create QUIC server config: {}", e))?;
 
-    // Configure Quinn
-    let mut server_config = ServerConfig::with_crypto(Arc::new(crypto));
-    let mut transport_config = quinn::TransportConfig::default();
-    transport_config.max_concurrent_uni_streams(0_u8.into());
-    server_config.transport = Arc::new(transport_config);
+    // Configure Quinn. HTTP/3 needs the peer's unidirectional control and QPACK streams,
+    // so the default stream limits are kept.
+    let server_config = ServerConfig::with_crypto(Arc::new(crypto));
 
     // Start the server
     let endpoint = Endpoint::server(server_config, addr)?;
 
     log::info!("HTTP/3 server listening on {}", endpoint.local_addr()?);
 
+    let connections = TaskTracker::new();
     loop {
-        if let Some(connecting) = endpoint.accept().await {
-            let conn = connecting.await?;
-            let gateway = Arc::clone(&gateway);
-
-            tokio::spawn(async move {
-                if let Err(e) = handle_http3_connection(conn, gateway).await {
-                    log::error!("HTTP/3 connection error: {}", e);
-                }
-            });
-        }
+        let incoming = tokio::select! {
+            incoming = endpoint.accept() => incoming,
+            _ = shutdown.triggered() => break,
+        };
+        let Some(incoming) = incoming else { break };
+        let router = router.clone();
+        let shutdown = shutdown.clone();
+
+        connections.spawn(async move {
+            if let Err(e) = handle_http3_connection(incoming, router, shutdown).await {
+                log::error!("HTTP/3 connection error: {}", e);
+            }
+        });
+    }
+
+    // Refuse new handshakes while the open connections send GOAWAY and drain
+    endpoint.set_server_config(None);
+    connections.close();
+    if tokio::time::timeout(shutdown.drain_timeout(), connections.wait())
+        .await
+        .is_err()
+    {
+        log::warn!("HTTP/3 connections still open after {:?}, closing them", shutdown.drain_timeout());
     }
+    endpoint.close(H3_NO_ERROR, b"server shutting down");
+    endpoint.wait_idle().await;
+
+    Ok(())
 }
 
-async fn handle_http3_connection(
-    conn: Connection,
-    gateway: Arc<tokio::sync::Mutex<GatewayServer>>,
-) -> Result<()> {
+async fn handle_http3_connection(incoming: Incoming, router: Router, shutdown: Shutdown) -> Result<()> {
+    let conn = incoming.await?;
+    let peer = conn
+        .peer_identity()
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+        .and_then(|chain| peer_certificate(Some(&chain)));
+    let quic_conn = conn.clone();
+    let mut h3_conn = h3::server::builder()
+        .build::<_, Bytes>(h3_quinn::Connection::new(conn))
+        .await?;
+
+    let requests = TaskTracker::new();
     loop {
-        let (mut send_stream, mut recv_stream) = conn.accept_bi().await?;
-
-        // Read request data with a reasonable size limit
-        let mut request_data = Vec::new();
-        let max_size = 1024 * 1024; // 1MB limit
-        let bytes_read = recv_stream.read_buf(&mut request_data).await?;
-
-        // Process the request via the gateway
-        let response_data = {
-            let gateway = gateway.lock().await;
-            // Example response - replace with actual gateway processing
-            let response = "HTTP/3 response";
-            response.as_bytes().to_vec()
+        let accepted = tokio::select! {
+            accepted = h3_conn.accept() => accepted,
+            _ = shutdown.triggered(), if !requests.is_closed() => {
+                // GOAWAY: no request past the ones already accepted will be served
+                h3_conn.shutdown(0).await?;
+                requests.close();
+                continue;
+            }
+            // h3 only ends accept() once the client goes away too, so idle peers are closed here
+            _ = requests.wait(), if requests.is_closed() => break,
         };
+        match accepted {
+            Ok(Some(resolver)) => {
+                let router = router.clone();
+                let peer = peer.clone();
+
+                requests.spawn(async move {
+                    if let Err(e) = handle_http3_request(resolver, router, peer).await {
+                        log::error!("HTTP/3 request error: {}", e);
+                    }
+                });
+            }
+            Ok(None) => break,
+            Err(e) if e.is_h3_no_error() => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if requests.is_closed() {
+        // CONNECTION_CLOSE discards unacknowledged stream data, so give the last responses a few
+        // round trips to arrive unless the client closes first
+        let linger = quic_conn.rtt() * 4 + CLOSE_LINGER_MIN;
+        let _ = tokio::time::timeout(linger, quic_conn.closed()).await;
+    }
+
+    // Dropping the connection sends CONNECTION_CLOSE with H3_NO_ERROR
+    Ok(())
+}
 
-        send_stream.write_all(&response_data).await?;
+async fn handle_http3_request<C>(
+    resolver: RequestResolver<C, Bytes>,
+    router: Router,
+    peer: Option<PeerCertificate>,
+) -> Result<()>
+where
+    C: h3::quic::Connection<Bytes>,
+{
+    let (mut req, mut stream) = resolver.resolve_request().await?;
+    if let Some(peer) = peer {
+        req.extensions_mut().insert(peer);
+    }
+
+    // Read request DATA frames with a reasonable size limit
+    let mut body = BytesMut::new();
+    while let Some(mut chunk) = stream.recv_data().await? {
+        if body.len() + chunk.remaining() > MAX_BODY_SIZE {
+            let response = GatewayError::PayloadTooLargeError(MAX_BODY_SIZE).to_response(&request_id(&req));
+            let (parts, body) = response.into_parts();
+            stream.send_response(Response::from_parts(parts, ())).await?;
+            stream.send_data(body).await?;
+            stream.finish().await?;
+            return Ok(());
+        }
+        body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+
+    if let Some(proxy) = router.grpc_proxy_for(&req) {
+        let response = proxy.forward(req.map(|()| buffered_body(body))).await;
+        return send_streamed_response(&mut stream, response).await;
+    }
+    if let Some(grpc_web) = router.grpc_web_for(&req) {
+        let response = grpc_web.handle(req.map(|()| buffered_body(body))).await;
+        return send_streamed_response(&mut stream, response).await;
+    }
+
+    // Process the request via the gateway
+    let (parts, ()) = req.into_parts();
+    let response = router
+        .handle(Request::from_parts(parts, body.freeze()))
+        .await;
+
+    let (parts, body) = response.into_parts();
 
-        // Finish the stream properly without awaiting a result
-        send_stream.finish()?;
+    stream.send_response(Response::from_parts(parts, ())).await?;
+    if !body.is_empty() {
+        stream.send_data(body).await?;
     }
+    stream.finish().await?;
+
+    Ok(())
+}
+
+fn buffered_body(body: BytesMut) -> ProxyBody {
+    Full::new(body.freeze()).map_err(|never| match never {}).boxed_unsync()
+}
+
+/// Relays a streamed response frame by frame, including the trailers gRPC puts its status in.
+async fn send_streamed_response<S>(
+    stream: &mut RequestStream<S, Bytes>,
+    response: Response<ProxyBody>,
+) -> Result<()>
+where
+    S: h3::quic::SendStream<Bytes>,
+{
+    let (parts, mut body) = response.into_parts();
+    stream.send_response(Response::from_parts(parts, ())).await?;
+
+    while let Some(frame) = body.frame().await {
+        let frame = frame?;
+        if frame.is_data() {
+            if let Ok(data) = frame.into_data() {
+                stream.send_data(data).await?;
+            }
+        } else if let Ok(trailers) = frame.into_trailers() {
+            stream.send_trailers(trailers).await?;
+        }
+    }
+
+    stream.finish().await?;
+    Ok(())
 }
@@ -0,0 +1,21 @@
+#![allow(clippy::module_inception)]
+
+pub mod errors;
+pub mod health;
+pub mod http3_serve;
+pub mod server;
+pub mod http2_serve;
+pub mod listener;
+pub mod proxy;
+pub mod router;
+pub mod shutdown;
+pub mod tls;
+pub mod transcoding;
+
+pub mod auth {
+    tonic::include_proto!("auth_service");
+}
+
+pub mod hello {
+    tonic::include_proto!("hello");
+}
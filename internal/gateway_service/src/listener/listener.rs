@@ -0,0 +1,59 @@
+use crate::tls::identity::IdentityFiles;
+use config::{AppConfig, ListenerConfig};
+use std::net::SocketAddr;
+
+/// A listener's settings after the section defaults have been applied.
+#[derive(Debug, Clone)]
+pub struct ListenerSettings {
+    pub address: SocketAddr,
+    pub cert_path: Option<String>,
+    pub chain_path: Option<String>,
+    pub key_path: Option<String>,
+    pub alpn: Vec<Vec<u8>>,
+}
+
+impl ListenerSettings {
+    /// Resolves a `[http2]` or `[http3]` section, or returns `None` if the listener is disabled.
+    ///
+    /// A missing address falls back to `[server].address`, missing certificate paths to the
+    /// `[tls]` section and a missing ALPN list to `default_alpn`. The `[tls]` chain file only
+    /// applies to the `[tls]` certificate.
+    pub fn resolve(section: Option<&ListenerConfig>, app: &AppConfig, default_alpn: &str) -> Option<Self> {
+        let section = section.cloned().unwrap_or(ListenerConfig {
+            enabled: true,
+            address: None,
+            cert_path: None,
+            chain_path: None,
+            key_path: None,
+            alpn: None,
+        });
+        if !section.enabled {
+            return None;
+        }
+
+        Some(Self {
+            address: section.address.unwrap_or(app.address),
+            chain_path: match section.cert_path {
+                Some(_) => section.chain_path,
+                None => section.chain_path.or_else(|| app.tls_chain_path.clone()),
+            },
+            cert_path: section.cert_path.or_else(|| app.tls_cert_path.clone()),
+            key_path: section.key_path.or_else(|| app.tls_key_path.clone()),
+            alpn: section
+                .alpn
+                .unwrap_or_else(|| vec![default_alpn.to_string()])
+                .into_iter()
+                .map(String::into_bytes)
+                .collect(),
+        })
+    }
+
+    /// The files to load the listener's certificate from, or `None` if none are configured.
+    pub fn identity_files(&self) -> Option<IdentityFiles> {
+        Some(IdentityFiles {
+            cert_path: self.cert_path.clone()?,
+            chain_path: self.chain_path.clone(),
+            key_path: self.key_path.clone()?,
+        })
+    }
+}
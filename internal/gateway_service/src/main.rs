@@ -1,19 +1,22 @@
-mod errors;
-mod handler;
-mod http3_serve;
-mod server;
-mod http2_serve;
-
-pub mod auth {
-    tonic::include_proto!("auth_service");
-}
-
-use config::{load_config, AppConfig};
-use http2_serve::http2_serve::run_http2_server;
-use http3_serve::http3_serve::run_http3_server;
+use config::load_config;
+use futures::future::{join_all, select_all, BoxFuture, FutureExt};
+use gateway_service::health::health::HealthMonitor;
+use gateway_service::http2_serve::http2_serve::run_http2_server;
+use gateway_service::http3_serve::http3_serve::run_http3_server;
+use gateway_service::listener::listener::ListenerSettings;
+use gateway_service::proxy::grpc_proxy::GrpcProxy;
+use gateway_service::proxy::grpc_web::GrpcWeb;
+use gateway_service::router::router::Router;
+use gateway_service::server::backoff::ConnectPolicy;
+use gateway_service::server::service::GatewayServer;
+use gateway_service::server::validate_cache::ValidateCache;
+use gateway_service::shutdown::shutdown::Shutdown;
+use gateway_service::tls::client_auth::client_verifier;
+use gateway_service::tls::identity::IdentityLoader;
+use gateway_service::tls::reload::spawn_reloader;
+use gateway_service::tls::upstream::client_tls_config;
+use gateway_service::transcoding::transcoder::Transcoder;
 use logger::init_logger;
-use server::service::GatewayServer;
-use std::net::SocketAddr;
 use std::sync::Arc;
 
 #[derive(Debug)]
@@ -77,42 +80,135 @@ async fn main() -> Result<(), AppError> {
         config.service_name
     );
 
-    let http2_addr: SocketAddr = "127.0.0.1:50053"
-        .parse()
-        .map_err(|e| AppError::Other(format!("Failed to parse HTTP/2 address: {}", e)))?;
-    let http3_addr: SocketAddr = "127.0.0.1:50054"
-        .parse()
-        .map_err(|e| AppError::Other(format!("Failed to parse HTTP/3 address: {}", e)))?;
+    let http2_settings = ListenerSettings::resolve(config.http2.as_ref(), &config, "h2");
+    let http3_settings = ListenerSettings::resolve(config.http3.as_ref(), &config, "h3");
+    if http2_settings.is_none() && http3_settings.is_none() {
+        return Err(AppError::Config("both [http2] and [http3] listeners are disabled".to_string()));
+    }
+
+    let client_verifier = match &config.tls_client_ca_path {
+        Some(ca_path) => Some(
+            client_verifier(ca_path, config.tls_client_auth)
+                .map_err(|e| AppError::Config(e.to_string()))?,
+        ),
+        None => None,
+    };
 
-    log::info!("HTTP/2 server will listen on {}", http2_addr);
-    log::info!("HTTP/3 server will listen on {}", http3_addr);
+    // Both listeners share one loader, so they present the same certificate
+    let identities = IdentityLoader::new(config.tls_dev_self_signed, config.tls_key_passphrase_env.as_deref())
+        .map_err(|e| AppError::Config(e.to_string()))?;
+    let tls_config = |settings: &ListenerSettings| {
+        identities
+            .load(settings.identity_files())
+            .map(|resolver| resolver.server_config(settings.alpn.clone(), client_verifier.clone()))
+            .map_err(|e| AppError::Config(e.to_string()))
+    };
+    let http2_listener = match http2_settings {
+        Some(settings) => Some((tls_config(&settings)?, settings)),
+        None => None,
+    };
+    let http3_listener = match http3_settings {
+        Some(settings) => Some((tls_config(&settings)?, settings)),
+        None => None,
+    };
+    spawn_reloader(identities.file_backed(), config.tls_watch)
+        .map_err(|e| AppError::Config(format!("Failed to set up TLS certificate reload: {}", e)))?;
+
+    let upstream_tls = match &config.upstream_tls {
+        Some(upstream_tls) => Some(client_tls_config(upstream_tls).map_err(|e| AppError::Config(e.to_string()))?),
+        None => None,
+    };
+
+    let connect = config
+        .upstream_connect
+        .as_ref()
+        .map(ConnectPolicy::from_config)
+        .unwrap_or_default();
 
     // Create Gateway server
     let gateway = GatewayServer::new(
         config
             .auth_service_address
             .unwrap_or_else(|| "http://127.0.0.1:50056".to_string()),
+        config
+            .hello_service_address
+            .unwrap_or_else(|| "http://127.0.0.1:50051".to_string()),
+        upstream_tls.as_ref(),
+        &connect,
     )
-        .await
+    .map_err(|e| AppError::Gateway(e.to_string()))?;
+    let gateway = match &config.validate_cache {
+        Some(cache_config) => gateway.with_validate_cache(ValidateCache::from_config(cache_config)),
+        None => gateway,
+    };
+
+    let gateway = Arc::new(gateway);
+    let grpc_proxy = GrpcProxy::from_config(gateway.clone(), &config.grpc_routes, upstream_tls.as_ref(), &connect)
         .map_err(|e| AppError::Gateway(e.to_string()))?;
+    let transcoder = Transcoder::new(&config.transcoding.unwrap_or_default(), grpc_proxy.clone())
+        .map_err(|e| AppError::Gateway(e.to_string()))?;
+    // Readiness follows AuthService, which every authenticated request goes through
+    let mut upstreams = vec![
+        (gateway.auth_upstream().clone(), true),
+        (gateway.hello_upstream().clone(), false),
+    ];
+    upstreams.extend(grpc_proxy.upstreams().into_iter().map(|upstream| (upstream, false)));
+    let health = Arc::new(HealthMonitor::new(upstreams, config.health.as_ref()));
+    health.spawn();
+
+    let mut router = Router::new(gateway)
+        .with_health(health.clone())
+        .with_grpc_proxy(grpc_proxy.clone())
+        .with_transcoder(transcoder);
+    let grpc_web_config = config.grpc_web.unwrap_or_default();
+    if grpc_web_config.enabled {
+        let grpc_web = GrpcWeb::new(grpc_proxy, &grpc_web_config).map_err(|e| AppError::Gateway(e.to_string()))?;
+        router = router.with_grpc_web(grpc_web);
+    }
 
-    let gateway = Arc::new(tokio::sync::Mutex::new(gateway));
-
-    // Start both HTTP/2 and HTTP/3 servers
-    let http3_future = run_http3_server(http3_addr, gateway.clone());
-    let http2_future = run_http2_server(http2_addr, gateway.clone());
+    let shutdown = Shutdown::new(config.shutdown.as_ref());
+    shutdown.trigger_on_signal(move || health.set_draining())?;
+
+    // Start the enabled listeners
+    let mut servers: Vec<BoxFuture<'static, Result<&'static str, AppError>>> = Vec::new();
+    if let Some((tls_config, settings)) = http2_listener {
+        log::info!("HTTP/2 server will listen on {}", settings.address);
+        let router = router.clone();
+        let shutdown = shutdown.clone();
+        servers.push(
+            async move {
+                run_http2_server(settings, tls_config, router, shutdown)
+                    .await
+                    .map_err(|e| AppError::Other(e.to_string()))?;
+                Ok("HTTP/2")
+            }
+            .boxed(),
+        );
+    }
+    if let Some((tls_config, settings)) = http3_listener {
+        log::info!("HTTP/3 server will listen on {}", settings.address);
+        let shutdown = shutdown.clone();
+        servers.push(
+            async move {
+                run_http3_server(settings, tls_config, router, shutdown)
+                    .await
+                    .map_err(|e| AppError::Other(e.to_string()))?;
+                Ok("HTTP/3")
+            }
+            .boxed(),
+        );
+    }
 
-    // Run servers concurrently
-    tokio::select! {
-        res = http3_future => {
-            res.map_err(|e| AppError::Other(e.to_string()))?;
-            log::info!("HTTP/3 server stopped");
-        }
-        res = http2_future => {
-            res.map_err(|e| AppError::Other(e.to_string()))?;
-            log::info!("HTTP/2 server stopped");
+    // Run servers concurrently; the first one to stop takes the others down with it
+    let (res, _, remaining) = select_all(servers).await;
+    shutdown.trigger();
+    for stopped in join_all(remaining).await {
+        match stopped {
+            Ok(name) => log::info!("{} server stopped", name),
+            Err(e) => log::error!("{}", e),
         }
     }
+    log::info!("{} server stopped", res?);
 
     Ok(())
 }
@@ -0,0 +1,342 @@
+use crate::errors::errors::GatewayError;
+use crate::router::auth::{authorize, Access};
+use crate::server::backoff::ConnectPolicy;
+use crate::server::service::{
+    GatewayServer, Identity, PeerCertificate, Upstream, CLIENT_CERT_SAN_METADATA,
+    CLIENT_CERT_SUBJECT_METADATA, USER_ID_METADATA, USER_ROLES_METADATA,
+};
+use config::GrpcRouteConfig;
+use futures::future::{poll_fn, BoxFuture};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{HeaderMap, Request, Response};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::transport::ClientTlsConfig;
+use tower::Service;
+
+/// Body type on both sides of the proxy: request bodies are streamed to the upstream and the
+/// upstream's frames, trailers included, are streamed back.
+pub type ProxyBody = BoxBody;
+
+struct GrpcRoute {
+    prefix: String,
+    access: Access,
+    upstream: Upstream,
+}
+
+/// Forwards gRPC calls by path prefix to the configured upstreams without decoding them, so a
+/// new service only needs a `[[grpc_routes]]` entry.
+#[derive(Clone)]
+pub struct GrpcProxy {
+    gateway: Arc<GatewayServer>,
+    routes: Arc<Vec<GrpcRoute>>,
+}
+
+impl GrpcProxy {
+    pub fn from_config(
+        gateway: Arc<GatewayServer>,
+        configs: &[GrpcRouteConfig],
+        tls: Option<&ClientTlsConfig>,
+        connect: &ConnectPolicy,
+    ) -> Result<Self, GatewayError> {
+        let mut routes = configs
+            .iter()
+            .map(|config| {
+                // Upstreams are dialled on first use so one that is down does not stop the gateway
+                let upstream = Upstream::connect(config.upstream.clone(), config.upstream.clone(), tls, connect)?;
+                if config.client_certificate && (config.authenticated || !config.roles.is_empty()) {
+                    return Err(GatewayError::ConfigError(format!(
+                        "grpc route {}: client_certificate cannot be combined with authenticated or roles",
+                        config.prefix
+                    )));
+                }
+                let access = if config.client_certificate {
+                    Access::Certificate
+                } else if !config.roles.is_empty() {
+                    Access::Roles(config.roles.clone())
+                } else if config.authenticated {
+                    Access::Authenticated
+                } else {
+                    Access::Public
+                };
+
+                log::info!("Proxying gRPC {} to {}", config.prefix, config.upstream);
+                Ok(GrpcRoute {
+                    prefix: config.prefix.trim_end_matches('*').to_string(),
+                    access,
+                    upstream,
+                })
+            })
+            .collect::<Result<Vec<_>, GatewayError>>()?;
+
+        // Longest prefix first, so a method-specific route wins over its service's route
+        routes.sort_by_key(|route| std::cmp::Reverse(route.prefix.len()));
+
+        Ok(Self {
+            gateway,
+            routes: Arc::new(routes),
+        })
+    }
+
+    /// One entry per distinct upstream address, named after it.
+    pub fn upstreams(&self) -> Vec<Upstream> {
+        let mut upstreams: Vec<Upstream> = Vec::new();
+        for route in self.routes.iter() {
+            if !upstreams.iter().any(|upstream| upstream.address == route.upstream.address) {
+                upstreams.push(route.upstream.clone());
+            }
+        }
+        upstreams
+    }
+
+    /// Whether the request is a gRPC call this proxy has a route for.
+    pub fn matches<B>(&self, req: &Request<B>) -> bool {
+        is_grpc(req.headers()) && self.route_for(req.uri().path()).is_some()
+    }
+
+    /// Forwards the call and returns the upstream response as is. Failures before the upstream
+    /// answers are reported as trailers-only gRPC responses.
+    pub async fn forward(&self, mut req: Request<ProxyBody>) -> Response<ProxyBody> {
+        let Some(route) = self.route_for(req.uri().path()) else {
+            return GatewayError::RouteNotFoundError(req.uri().path().to_string())
+                .to_status()
+                .into_http();
+        };
+
+        if let Err(e) = self.authorize(route, &mut req).await {
+            if e.status_code().is_server_error() {
+                log::error!("Authorizing gRPC call to {} failed: {}", route.prefix, e);
+            }
+            return e.to_status().into_http();
+        }
+
+        let mut channel = route.upstream.channel.clone();
+        let result = match poll_fn(|cx| channel.poll_ready(cx)).await {
+            Ok(()) => channel.call(req).await,
+            Err(e) => Err(e),
+        };
+
+        result.unwrap_or_else(|e| {
+            log::error!("gRPC upstream for {} failed: {}", route.prefix, e);
+            GatewayError::TransportError(e).to_status().into_http()
+        })
+    }
+
+    /// Enforces the route's access rule and replaces any identity headers the client sent with
+    /// the ones the gateway vouches for.
+    async fn authorize<B>(&self, route: &GrpcRoute, req: &mut Request<B>) -> Result<(), GatewayError> {
+        // Identity headers are only trusted from the gateway, never from the client
+        let headers = req.headers_mut();
+        headers.remove(USER_ID_METADATA);
+        headers.remove(USER_ROLES_METADATA);
+        headers.remove(CLIENT_CERT_SUBJECT_METADATA);
+        headers.remove(CLIENT_CERT_SAN_METADATA);
+
+        let peer = req.extensions().get::<PeerCertificate>().cloned();
+        if let Some(peer) = &peer {
+            insert_peer_certificate(req.headers_mut(), peer)?;
+        }
+        if let Some(identity) = authorize(&self.gateway, &route.access, req.headers(), peer.as_ref()).await? {
+            insert_identity(req.headers_mut(), &identity)?;
+        }
+        Ok(())
+    }
+
+    pub fn has_route(&self, path: &str) -> bool {
+        self.route_for(path).is_some()
+    }
+
+    fn route_for(&self, path: &str) -> Option<&GrpcRoute> {
+        self.routes.iter().find(|route| path.starts_with(&route.prefix))
+    }
+}
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value == "application/grpc" || value.starts_with("application/grpc+"))
+}
+
+fn insert_identity(headers: &mut HeaderMap, identity: &Identity) -> Result<(), GatewayError> {
+    headers.insert(USER_ID_METADATA, metadata_value(&identity.user_id)?);
+    headers.insert(USER_ROLES_METADATA, metadata_value(&identity.roles.join(","))?);
+    Ok(())
+}
+
+fn insert_peer_certificate(headers: &mut HeaderMap, peer: &PeerCertificate) -> Result<(), GatewayError> {
+    for (key, value) in peer.metadata() {
+        headers.insert(key, metadata_value(&value)?);
+    }
+    Ok(())
+}
+
+/// Rejects the call rather than forwarding it without the identity the upstream relies on.
+fn metadata_value(value: &str) -> Result<HeaderValue, GatewayError> {
+    HeaderValue::from_str(value).map_err(|_| GatewayError::InternalError("identity is not valid metadata".to_string()))
+}
+
+/// Lets the proxy sit under tower middleware such as the gRPC-Web translation layer.
+impl Service<Request<ProxyBody>> for GrpcProxy {
+    type Response = Response<ProxyBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<ProxyBody>) -> Self::Future {
+        let proxy = self.clone();
+        Box::pin(async move { Ok(proxy.forward(req).await) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::ValidateResponse;
+    use crate::server::validate_cache::ValidateCache;
+    use hyper::header::AUTHORIZATION;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    const UNREACHABLE: &str = "http://127.0.0.1:1";
+
+    fn route(prefix: &str, upstream: &str) -> GrpcRouteConfig {
+        GrpcRouteConfig {
+            prefix: prefix.to_string(),
+            upstream: upstream.to_string(),
+            authenticated: false,
+            roles: Vec::new(),
+            client_certificate: false,
+        }
+    }
+
+    /// A proxy whose upstreams are never dialled; bearer tokens are answered from the validate
+    /// cache.
+    fn proxy(routes: &[GrpcRouteConfig]) -> GrpcProxy {
+        let cache = ValidateCache::new(16, Duration::from_secs(60), Duration::from_secs(60));
+        let expires_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64 + 3600;
+        for (token, user_id, role) in [
+            ("user-token", "user-1", "user"),
+            ("admin-token", "admin-1", "admin"),
+            ("odd-token", "user\n1", "user"),
+        ] {
+            cache.insert(
+                token,
+                &ValidateResponse {
+                    valid: true,
+                    user_id: user_id.to_string(),
+                    roles: vec![role.to_string()],
+                    expires_at,
+                },
+            );
+        }
+
+        let gateway = GatewayServer::new(UNREACHABLE.to_string(), UNREACHABLE.to_string(), None, &ConnectPolicy::default())
+            .unwrap()
+            .with_validate_cache(cache);
+        GrpcProxy::from_config(Arc::new(gateway), routes, None, &ConnectPolicy::default()).unwrap()
+    }
+
+    fn request(path: &str, token: Option<&str>) -> Request<ProxyBody> {
+        let mut builder = Request::builder()
+            .uri(path)
+            .header(CONTENT_TYPE, "application/grpc")
+            .header(USER_ID_METADATA, "forged")
+            .header(USER_ROLES_METADATA, "admin")
+            .header(CLIENT_CERT_SUBJECT_METADATA, "CN=forged");
+        if let Some(token) = token {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        builder.body(ProxyBody::default()).unwrap()
+    }
+
+    async fn grpc_status(proxy: &GrpcProxy, req: Request<ProxyBody>) -> String {
+        let response = proxy.forward(req).await;
+        response.headers()["grpc-status"].to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn longest_prefix_wins_regardless_of_config_order() {
+        let proxy = proxy(&[
+            route("/hello.Greeter/*", "http://127.0.0.1:1001"),
+            route("/hello.Greeter/SayHello", "http://127.0.0.1:1002"),
+        ]);
+
+        let upstream = |path| proxy.route_for(path).map(|route| route.upstream.address.as_str());
+        assert_eq!(upstream("/hello.Greeter/SayHello"), Some("http://127.0.0.1:1002"));
+        assert_eq!(upstream("/hello.Greeter/SayGoodbye"), Some("http://127.0.0.1:1001"));
+        assert_eq!(upstream("/other.Service/Call"), None);
+        assert!(proxy.matches(&request("/hello.Greeter/SayHello", None)));
+        assert!(!proxy.matches(&request("/other.Service/Call", None)));
+    }
+
+    #[tokio::test]
+    async fn strips_client_supplied_identity_on_public_routes() {
+        let proxy = proxy(&[route("/hello.Greeter/*", UNREACHABLE)]);
+        let mut req = request("/hello.Greeter/SayHello", None);
+
+        proxy.authorize(&proxy.routes[0], &mut req).await.unwrap();
+
+        for header in [USER_ID_METADATA, USER_ROLES_METADATA, CLIENT_CERT_SUBJECT_METADATA] {
+            assert!(!req.headers().contains_key(header), "{} was forwarded", header);
+        }
+    }
+
+    #[tokio::test]
+    async fn replaces_client_supplied_identity_with_the_token_identity() {
+        let proxy = proxy(&[GrpcRouteConfig {
+            authenticated: true,
+            ..route("/hello.Greeter/*", UNREACHABLE)
+        }]);
+        let mut req = request("/hello.Greeter/SayHello", Some("user-token"));
+
+        proxy.authorize(&proxy.routes[0], &mut req).await.unwrap();
+
+        assert_eq!(req.headers()[USER_ID_METADATA], "user-1");
+        assert_eq!(req.headers()[USER_ROLES_METADATA], "user");
+        assert!(!req.headers().contains_key(CLIENT_CERT_SUBJECT_METADATA));
+    }
+
+    #[tokio::test]
+    async fn enforces_per_route_access() {
+        let proxy = proxy(&[
+            GrpcRouteConfig {
+                authenticated: true,
+                ..route("/hello.Greeter/*", UNREACHABLE)
+            },
+            GrpcRouteConfig {
+                roles: vec!["admin".to_string()],
+                ..route("/admin.Admin/*", UNREACHABLE)
+            },
+            GrpcRouteConfig {
+                client_certificate: true,
+                ..route("/internal.Billing/*", UNREACHABLE)
+            },
+        ]);
+
+        // Unauthenticated (16) and PermissionDenied (7) are answered before any upstream is dialled
+        assert_eq!(grpc_status(&proxy, request("/hello.Greeter/SayHello", None)).await, "16");
+        assert_eq!(grpc_status(&proxy, request("/admin.Admin/Reset", None)).await, "16");
+        assert_eq!(grpc_status(&proxy, request("/admin.Admin/Reset", Some("user-token"))).await, "7");
+        assert_eq!(grpc_status(&proxy, request("/internal.Billing/Charge", Some("admin-token"))).await, "16");
+
+        let mut admin = request("/admin.Admin/Reset", Some("admin-token"));
+        proxy.authorize(proxy.route_for("/admin.Admin/Reset").unwrap(), &mut admin).await.unwrap();
+        assert_eq!(admin.headers()[USER_ID_METADATA], "admin-1");
+    }
+
+    #[tokio::test]
+    async fn rejects_an_identity_that_is_not_valid_metadata() {
+        let proxy = proxy(&[GrpcRouteConfig {
+            authenticated: true,
+            ..route("/hello.Greeter/*", UNREACHABLE)
+        }]);
+
+        // Internal (13): the call is refused rather than forwarded without the caller's identity
+        assert_eq!(grpc_status(&proxy, request("/hello.Greeter/SayHello", Some("odd-token"))).await, "13");
+    }
+}
@@ -0,0 +1,142 @@
+use super::grpc_proxy::{GrpcProxy, ProxyBody};
+use crate::errors::errors::GatewayError;
+use config::GrpcWebConfig;
+use futures::future::poll_fn;
+use http_body_util::{BodyExt, BodyStream, StreamBody};
+use hyper::header::{HeaderName, HeaderValue, ACCESS_CONTROL_REQUEST_METHOD, CONTENT_TYPE, ORIGIN};
+use hyper::{Method, Request, Response};
+use std::time::Duration;
+use tonic::Status;
+use tonic_web::{GrpcWebLayer, GrpcWebService};
+use tower::{Layer, Service};
+use tower_http::cors::{AllowOrigin, Cors, CorsLayer};
+
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+// The gRPC-Web defaults plus `authorization`, which the bearer token travels in
+const ALLOWED_HEADERS: [&str; 5] = ["x-grpc-web", "content-type", "x-user-agent", "grpc-timeout", "authorization"];
+
+/// Serves `application/grpc-web` and `application/grpc-web-text` calls for the routes of the
+/// gRPC proxy, together with the CORS preflights browsers send before them.
+#[derive(Clone)]
+pub struct GrpcWeb {
+    proxy: GrpcProxy,
+    service: Cors<GrpcWebService<GrpcProxy>>,
+}
+
+impl GrpcWeb {
+    /// Fails when no origin is listed: gRPC-Web is only reachable from browsers, and allowing
+    /// every origin would let any site call the proxied services.
+    pub fn new(proxy: GrpcProxy, config: &GrpcWebConfig) -> Result<Self, GatewayError> {
+        if config.allowed_origins.is_empty() {
+            return Err(GatewayError::ConfigError(
+                "grpc_web.allowed_origins must list at least one origin".to_string(),
+            ));
+        }
+        let origins = config
+            .allowed_origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|_| GatewayError::ConfigError(format!("invalid gRPC-Web origin '{}'", origin)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let cors = CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods([Method::POST])
+            .max_age(PREFLIGHT_MAX_AGE)
+            .expose_headers([Status::GRPC_STATUS, Status::GRPC_MESSAGE, Status::GRPC_STATUS_DETAILS])
+            .allow_headers(ALLOWED_HEADERS.map(HeaderName::from_static));
+
+        Ok(Self {
+            service: cors.layer(GrpcWebLayer::new().layer(proxy.clone())),
+            proxy,
+        })
+    }
+
+    /// Whether the request is a gRPC-Web call, or its preflight, for a proxied path.
+    pub fn matches<B>(&self, req: &Request<B>) -> bool {
+        let is_grpc_web = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/grpc-web"));
+        let is_preflight = req.method() == Method::OPTIONS
+            && req.headers().contains_key(ORIGIN)
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+
+        (is_grpc_web || is_preflight) && self.proxy.has_route(req.uri().path())
+    }
+
+    pub async fn handle(&self, req: Request<ProxyBody>) -> Response<ProxyBody> {
+        // Decoding grpc-web-text shrinks the body, but the decoder passes the exact size hint of
+        // the base64 body through, and the upstream resets a stream shorter than its announced
+        // length. Re-wrapping the body drops the hint.
+        let req = req.map(|body| StreamBody::new(BodyStream::new(body)).boxed_unsync());
+
+        let mut service = self.service.clone();
+        let result = match poll_fn(|cx| service.poll_ready(cx)).await {
+            Ok(()) => service.call(req).await,
+            Err(e) => Err(e),
+        };
+        // The proxy underneath never fails, it reports errors as gRPC statuses
+        result.unwrap_or_else(|never| match never {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::backoff::ConnectPolicy;
+    use crate::server::service::GatewayServer;
+    use config::GrpcRouteConfig;
+    use hyper::header::{ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_ORIGIN};
+    use std::sync::Arc;
+
+    fn grpc_web(allowed_origins: &[&str]) -> Result<GrpcWeb, GatewayError> {
+        let unreachable = "http://127.0.0.1:1".to_string();
+        let gateway = GatewayServer::new(unreachable.clone(), unreachable.clone(), None, &ConnectPolicy::default())
+            .unwrap();
+        let routes = [GrpcRouteConfig {
+            prefix: "/hello.HelloService/*".to_string(),
+            upstream: unreachable,
+            authenticated: false,
+            roles: Vec::new(),
+            client_certificate: false,
+        }];
+        let proxy = GrpcProxy::from_config(Arc::new(gateway), &routes, None, &ConnectPolicy::default()).unwrap();
+        let config = GrpcWebConfig {
+            enabled: true,
+            allowed_origins: allowed_origins.iter().map(|origin| origin.to_string()).collect(),
+        };
+        GrpcWeb::new(proxy, &config)
+    }
+
+    fn preflight(origin: &str) -> Request<ProxyBody> {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/hello.HelloService/SayHello")
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(http_body_util::Empty::new().map_err(|never| match never {}).boxed_unsync())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn refuses_to_start_without_allowed_origins() {
+        assert!(matches!(grpc_web(&[]), Err(GatewayError::ConfigError(_))));
+        assert!(matches!(grpc_web(&["bad\norigin"]), Err(GatewayError::ConfigError(_))));
+    }
+
+    #[tokio::test]
+    async fn preflight_allows_only_listed_origins_without_credentials() {
+        let grpc_web = grpc_web(&["https://app.example.com"]).unwrap();
+
+        let allowed = grpc_web.handle(preflight("https://app.example.com")).await;
+        assert_eq!(allowed.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert!(!allowed.headers().contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+
+        let denied = grpc_web.handle(preflight("https://evil.example.com")).await;
+        assert!(!denied.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod grpc_proxy;
+pub mod grpc_web;
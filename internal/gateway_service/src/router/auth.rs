@@ -0,0 +1,67 @@
+use crate::errors::errors::GatewayError;
+use crate::server::service::{GatewayServer, Identity, PeerCertificate};
+use hyper::header::AUTHORIZATION;
+use hyper::HeaderMap;
+
+/// What a route requires from the caller before its handler runs.
+#[derive(Debug, Clone, Default)]
+pub enum Access {
+    #[default]
+    Public,
+    Authenticated,
+    /// Authenticated and holding every one of the listed roles.
+    Roles(Vec<String>),
+    /// A verified client certificate instead of a bearer token. The caller is identified by the
+    /// certificate's subject and holds no roles.
+    Certificate,
+}
+
+/// Enforces a route's access rule, returning the caller's identity when the route needs one.
+/// The identity carries the client certificate whenever the connection presented one.
+pub async fn authorize(
+    gateway: &GatewayServer,
+    access: &Access,
+    headers: &HeaderMap,
+    peer: Option<&PeerCertificate>,
+) -> Result<Option<Identity>, GatewayError> {
+    let required_roles: &[String] = match access {
+        Access::Public => return Ok(None),
+        Access::Certificate => return certificate_identity(peer).map(Some),
+        Access::Authenticated => &[],
+        Access::Roles(roles) => roles,
+    };
+
+    let token = bearer_token(headers)?;
+    let identity = gateway.authenticate(token).await?;
+
+    if let Some(missing) = required_roles.iter().find(|role| !identity.has_role(role)) {
+        return Err(GatewayError::PermissionDeniedError(format!("role '{}' is required", missing)));
+    }
+    Ok(Some(Identity {
+        client_certificate: peer.cloned(),
+        ..identity
+    }))
+}
+
+fn certificate_identity(peer: Option<&PeerCertificate>) -> Result<Identity, GatewayError> {
+    let peer = peer.ok_or_else(|| GatewayError::UnauthenticatedError("client certificate required".to_string()))?;
+    Ok(Identity {
+        user_id: peer.subject.clone(),
+        roles: Vec::new(),
+        client_certificate: Some(peer.clone()),
+    })
+}
+
+fn bearer_token(headers: &HeaderMap) -> Result<&str, GatewayError> {
+    let value = headers
+        .get(AUTHORIZATION)
+        .ok_or_else(|| GatewayError::UnauthenticatedError("missing Authorization header".to_string()))?;
+
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| GatewayError::UnauthenticatedError("expected a Bearer token".to_string()))
+}
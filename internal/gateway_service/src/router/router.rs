@@ -0,0 +1,413 @@
+use super::auth::{authorize, Access};
+use crate::errors::errors::GatewayError;
+use crate::health::health::HealthMonitor;
+use crate::proxy::grpc_proxy::GrpcProxy;
+use crate::proxy::grpc_web::GrpcWeb;
+use crate::transcoding::transcoder::Transcoder;
+use crate::server::service::{GatewayServer, Identity, PeerCertificate};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{HeaderMap, Method, Request, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Largest request body either listener buffers for [`Router::handle`].
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+pub type HandlerResult<T> = Result<T, GatewayError>;
+
+type Handler = Arc<
+    dyn Fn(Arc<GatewayServer>, Option<Identity>, Bytes) -> BoxFuture<'static, HandlerResult<Response<Bytes>>>
+        + Send
+        + Sync,
+>;
+
+pub fn json_response<T: Serialize>(value: &T) -> HandlerResult<Response<Bytes>> {
+    let json = serde_json::to_vec(value).map_err(|e| GatewayError::InternalError(e.to_string()))?;
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Bytes::from(json))
+        .map_err(|e| GatewayError::InternalError(e.to_string()))
+}
+
+/// A single entry of the route table: a method and exact path bound to a type-erased handler.
+pub struct Route {
+    method: Method,
+    path: &'static str,
+    access: Access,
+    handler: Handler,
+}
+
+impl Route {
+    /// Builds a route whose body is decoded from JSON into `Req` and whose result is encoded back as JSON.
+    pub fn json<Req, Res, F, Fut>(method: Method, path: &'static str, handler: F) -> Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Res: Serialize + 'static,
+        F: Fn(Arc<GatewayServer>, Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult<Res>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let handler: Handler = Arc::new(move |gateway, _, body: Bytes| {
+            let handler = handler.clone();
+            Box::pin(async move { json_response(&handler(gateway, parse_json(&body)?).await?) })
+        });
+
+        Self {
+            method,
+            path,
+            access: Access::Public,
+            handler,
+        }
+    }
+
+    /// Like [`Route::json`], but only reachable with a valid bearer token. The handler receives
+    /// the caller's identity.
+    pub fn authenticated_json<Req, Res, F, Fut>(method: Method, path: &'static str, handler: F) -> Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Res: Serialize + 'static,
+        F: Fn(Arc<GatewayServer>, Identity, Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult<Res>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let handler: Handler = Arc::new(move |gateway, identity: Option<Identity>, body: Bytes| {
+            let handler = handler.clone();
+            Box::pin(async move {
+                let identity = identity
+                    .ok_or_else(|| GatewayError::UnauthenticatedError("authentication required".to_string()))?;
+                json_response(&handler(gateway, identity, parse_json(&body)?).await?)
+            })
+        });
+
+        Self {
+            method,
+            path,
+            access: Access::Authenticated,
+            handler,
+        }
+    }
+
+    /// Like [`Route::authenticated_json`], but the caller is identified by the verified client
+    /// certificate of the connection instead of a bearer token.
+    pub fn certificate_json<Req, Res, F, Fut>(method: Method, path: &'static str, handler: F) -> Self
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Res: Serialize + 'static,
+        F: Fn(Arc<GatewayServer>, Identity, Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult<Res>> + Send + 'static,
+    {
+        Self {
+            access: Access::Certificate,
+            ..Self::authenticated_json(method, path, handler)
+        }
+    }
+
+    /// Additionally requires the caller to hold every one of `roles`.
+    ///
+    /// Panics on a [`Route::certificate_json`] route: certificate callers hold no roles, and
+    /// replacing its access rule would silently switch the route to bearer tokens.
+    pub fn require_roles(mut self, roles: &[&'static str]) -> Self {
+        assert!(
+            !matches!(self.access, Access::Certificate),
+            "{} {} authenticates by client certificate and cannot require roles",
+            self.method,
+            self.path
+        );
+        self.access = Access::Roles(roles.iter().map(|role| role.to_string()).collect());
+        self
+    }
+}
+
+/// An empty body is read as JSON `null`, so bodiless requests such as GETs can use `()` as `Req`.
+fn parse_json<Req: DeserializeOwned>(body: &Bytes) -> HandlerResult<Req> {
+    let body: &[u8] = if body.is_empty() { b"null" } else { body };
+    serde_json::from_slice::<Req>(body).map_err(|e| GatewayError::InvalidRequestError(format!("Invalid JSON: {}", e)))
+}
+
+/// Transport-agnostic dispatcher shared by the HTTP/2 and HTTP/3 listeners.
+///
+/// It works on fully buffered bodies, so it can be driven without any socket or TLS setup.
+#[derive(Clone)]
+pub struct Router {
+    gateway: Arc<GatewayServer>,
+    routes: Arc<Vec<Route>>,
+    grpc_proxy: Option<GrpcProxy>,
+    grpc_web: Option<GrpcWeb>,
+    transcoder: Option<Transcoder>,
+    health: Option<Arc<HealthMonitor>>,
+}
+
+impl Router {
+    pub fn new(gateway: Arc<GatewayServer>) -> Self {
+        Self::with_routes(gateway, super::routes::routes())
+    }
+
+    pub fn with_routes(gateway: Arc<GatewayServer>, routes: Vec<Route>) -> Self {
+        Self {
+            gateway,
+            routes: Arc::new(routes),
+            grpc_proxy: None,
+            grpc_web: None,
+            transcoder: None,
+            health: None,
+        }
+    }
+
+    pub fn with_grpc_web(mut self, grpc_web: GrpcWeb) -> Self {
+        self.grpc_web = Some(grpc_web);
+        self
+    }
+
+    /// The gRPC-Web bridge, if the request is a gRPC-Web call or preflight it should serve.
+    pub fn grpc_web_for<B>(&self, req: &Request<B>) -> Option<&GrpcWeb> {
+        self.grpc_web.as_ref().filter(|grpc_web| grpc_web.matches(req))
+    }
+
+    /// Serves transcoded routes for paths the static route table does not have.
+    pub fn with_transcoder(mut self, transcoder: Transcoder) -> Self {
+        self.transcoder = Some(transcoder);
+        self
+    }
+
+    /// Serves the `/healthz` and `/readyz` probes.
+    pub fn with_health(mut self, health: Arc<HealthMonitor>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    pub fn with_grpc_proxy(mut self, proxy: GrpcProxy) -> Self {
+        self.grpc_proxy = Some(proxy);
+        self
+    }
+
+    /// The gRPC proxy, if the request is a gRPC call it should forward. Such requests are
+    /// streamed by the listener instead of being buffered for [`Router::handle`].
+    pub fn grpc_proxy_for<B>(&self, req: &Request<B>) -> Option<&GrpcProxy> {
+        self.grpc_proxy.as_ref().filter(|proxy| proxy.matches(req))
+    }
+
+    /// Routes a request and tags the response with its request id, taken from the
+    /// `x-request-id` header or generated when the client did not send one.
+    pub async fn handle(&self, req: Request<Bytes>) -> Response<Bytes> {
+        let request_id = request_id(&req);
+        let (parts, body) = req.into_parts();
+
+        let peer = parts.extensions.get::<PeerCertificate>();
+        let mut response = match self.dispatch(&parts.method, parts.uri.path(), &parts.headers, peer, body).await {
+            Ok(response) => response,
+            Err(e) => {
+                if e.status_code().is_server_error() {
+                    log::error!("Request {} to {} failed: {}", request_id, parts.uri.path(), e);
+                }
+                e.to_response(&request_id)
+            }
+        };
+
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        response
+    }
+
+    async fn dispatch(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        peer: Option<&PeerCertificate>,
+        body: Bytes,
+    ) -> HandlerResult<Response<Bytes>> {
+        if let Some(response) = self.health.as_ref().and_then(|health| health.handle(method, path)) {
+            return response;
+        }
+
+        let mut path_matched = false;
+
+        for route in self.routes.iter().filter(|route| route.path == path) {
+            if route.method == *method {
+                let identity = authorize(&self.gateway, &route.access, headers, peer).await?;
+                return (route.handler)(self.gateway.clone(), identity, body).await;
+            }
+            path_matched = true;
+        }
+
+        if let Some(transcoder) = self.transcoder.as_ref().filter(|_| !path_matched) {
+            if let Some(result) = transcoder.handle(method, path, headers, peer, body).await {
+                return result;
+            }
+            path_matched = transcoder.has_path(path);
+        }
+
+        if path_matched {
+            Err(GatewayError::MethodNotAllowedError(method.to_string()))
+        } else {
+            Err(GatewayError::RouteNotFoundError(path.to_string()))
+        }
+    }
+}
+
+pub fn request_id<B>(req: &Request<B>) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::ValidateResponse;
+    use crate::server::backoff::ConnectPolicy;
+    use crate::server::validate_cache::ValidateCache;
+    use hyper::header::AUTHORIZATION;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    const USER_TOKEN: &str = "user-token";
+
+    /// A gateway whose upstreams are never dialled: the only token it accepts is answered from
+    /// the validate cache.
+    fn router() -> Router {
+        let cache = ValidateCache::new(16, Duration::from_secs(60), Duration::from_secs(60));
+        let expires_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64 + 3600;
+        cache.insert(
+            USER_TOKEN,
+            &ValidateResponse {
+                valid: true,
+                user_id: "user-1".to_string(),
+                roles: vec!["user".to_string()],
+                expires_at,
+            },
+        );
+
+        let unreachable = "http://127.0.0.1:1".to_string();
+        let gateway = GatewayServer::new(unreachable.clone(), unreachable, None, &ConnectPolicy::default())
+            .unwrap()
+            .with_validate_cache(cache);
+
+        let routes = vec![
+            Route::json(Method::GET, "/ping", |_, ()| async { Ok("pong") }),
+            Route::authenticated_json(Method::GET, "/me", |_, identity: Identity, ()| async move {
+                Ok(identity.user_id)
+            }),
+            Route::authenticated_json(Method::GET, "/admin", |_, _, ()| async { Ok("admin") })
+                .require_roles(&["admin"]),
+            Route::authenticated_json(Method::GET, "/me/certificate", |_, identity: Identity, ()| async move {
+                Ok(identity.client_certificate.map(|cert| cert.subject))
+            }),
+            Route::certificate_json(Method::GET, "/internal", |_, identity: Identity, ()| async move {
+                Ok(identity.user_id)
+            }),
+        ];
+        Router::with_routes(Arc::new(gateway), routes)
+    }
+
+    fn request(method: Method, path: &str, token: Option<&str>) -> Request<Bytes> {
+        let mut builder = Request::builder().method(method).uri(path);
+        if let Some(token) = token {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        builder.body(Bytes::new()).unwrap()
+    }
+
+    async fn call(method: Method, path: &str, token: Option<&str>) -> (StatusCode, String) {
+        let response = router().handle(request(method, path, token)).await;
+        (response.status(), String::from_utf8_lossy(response.body()).into_owned())
+    }
+
+    #[tokio::test]
+    async fn matching_route_runs_its_handler() {
+        assert_eq!(call(Method::GET, "/ping", None).await, (StatusCode::OK, "\"pong\"".to_string()));
+    }
+
+    #[tokio::test]
+    async fn unknown_path_is_not_found() {
+        let (status, body) = call(Method::GET, "/missing", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("\"NOT_FOUND\""), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn known_path_with_other_method_is_not_allowed() {
+        let (status, _) = call(Method::POST, "/ping", None).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn authenticated_route_without_token_is_unauthorized() {
+        let response = router().handle(request(Method::GET, "/me", None)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    }
+
+    #[tokio::test]
+    async fn authenticated_route_sees_the_caller() {
+        assert_eq!(
+            call(Method::GET, "/me", Some(USER_TOKEN)).await,
+            (StatusCode::OK, "\"user-1\"".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_role_is_forbidden() {
+        let (status, body) = call(Method::GET, "/admin", Some(USER_TOKEN)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("role 'admin' is required"), "{}", body);
+    }
+
+    fn with_peer(mut req: Request<Bytes>) -> Request<Bytes> {
+        req.extensions_mut().insert(PeerCertificate {
+            subject: "CN=billing,O=internal".to_string(),
+            sans: vec!["DNS:billing.internal".to_string()],
+        });
+        req
+    }
+
+    #[tokio::test]
+    async fn certificate_route_without_certificate_is_unauthorized() {
+        let (status, body) = call(Method::GET, "/internal", Some(USER_TOKEN)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("client certificate required"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn certificate_route_identifies_the_caller_by_subject() {
+        let response = router().handle(with_peer(request(Method::GET, "/internal", None))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "\"CN=billing,O=internal\"");
+    }
+
+    #[tokio::test]
+    async fn bearer_identity_carries_the_client_certificate() {
+        let response = router()
+            .handle(with_peer(request(Method::GET, "/me/certificate", Some(USER_TOKEN))))
+            .await;
+        assert_eq!(response.body(), "\"CN=billing,O=internal\"");
+
+        let (_, body) = call(Method::GET, "/me/certificate", Some(USER_TOKEN)).await;
+        assert_eq!(body, "null");
+    }
+
+    #[tokio::test]
+    async fn responses_carry_the_request_id() {
+        let mut req = request(Method::GET, "/ping", None);
+        req.headers_mut().insert(REQUEST_ID_HEADER, HeaderValue::from_static("abc"));
+        let response = router().handle(req).await;
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc");
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot require roles")]
+    fn certificate_routes_cannot_require_roles() {
+        let _ = Route::certificate_json(Method::GET, "/internal", |_, _, ()| async { Ok("internal") })
+            .require_roles(&["admin"]);
+    }
+}
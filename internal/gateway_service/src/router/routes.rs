@@ -0,0 +1,246 @@
+use crate::auth::{
+    GenerateTokensRequest, LoginRequest, LogoutRequest, RefreshRequest, RegisterRequest,
+    ValidateRequest,
+};
+use crate::errors::errors::GatewayError;
+use crate::hello::HelloRequest;
+use crate::router::router::{HandlerResult, Route};
+use crate::server::service::{GatewayServer, Identity};
+use crate::server::validate_cache::ValidateCacheStats;
+use hyper::Method;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpLoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpRefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpLoginResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpRefreshResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpRegisterRequest {
+    pub username: String,
+    pub password: String,
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpRegisterResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpValidateRequest {
+    pub access_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpValidateResponse {
+    pub valid: bool,
+    pub user_id: String,
+    pub roles: Vec<String>,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpLogoutRequest {
+    pub username: String,
+    pub access_token: String,
+    #[serde(default)]
+    pub all_sessions: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpLogoutResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpGenerateTokensRequest {
+    pub user_id: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpGenerateTokensResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpHelloRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpHelloResponse {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpClientCertificateResponse {
+    pub subject: String,
+    pub sans: Vec<String>,
+}
+
+/// The gateway's route table. Every entry is served over both HTTP/2 and HTTP/3.
+pub fn routes() -> Vec<Route> {
+    vec![
+        Route::json(Method::POST, "/login", handle_login),
+        Route::json(Method::POST, "/refresh", handle_refresh),
+        Route::json(Method::POST, "/register", handle_register),
+        Route::json(Method::POST, "/validate", handle_validate),
+        Route::json(Method::POST, "/logout", handle_logout),
+        // Mints tokens for any user with any roles, so only admins may call it
+        Route::json(Method::POST, "/generate-tokens", handle_generate_tokens).require_roles(&["admin"]),
+        Route::authenticated_json(Method::POST, "/hello", handle_hello),
+        Route::json(Method::GET, "/stats/validate-cache", handle_validate_cache_stats).require_roles(&["admin"]),
+        Route::certificate_json(Method::GET, "/client-certificate", handle_client_certificate),
+    ]
+}
+
+async fn handle_login(
+    gateway: Arc<GatewayServer>,
+    req: HttpLoginRequest,
+) -> HandlerResult<HttpLoginResponse> {
+    let grpc_req = LoginRequest {
+        username: req.username,
+        password: req.password,
+    };
+    let grpc_res = gateway.login(grpc_req).await?;
+    Ok(HttpLoginResponse {
+        access_token: grpc_res.access_token,
+        refresh_token: grpc_res.refresh_token,
+    })
+}
+
+async fn handle_refresh(
+    gateway: Arc<GatewayServer>,
+    req: HttpRefreshRequest,
+) -> HandlerResult<HttpRefreshResponse> {
+    let grpc_req = RefreshRequest {
+        refresh_token: req.refresh_token,
+    };
+    let grpc_res = gateway.refresh(grpc_req).await?;
+    Ok(HttpRefreshResponse {
+        access_token: grpc_res.access_token,
+        refresh_token: grpc_res.refresh_token,
+    })
+}
+
+async fn handle_register(
+    gateway: Arc<GatewayServer>,
+    req: HttpRegisterRequest,
+) -> HandlerResult<HttpRegisterResponse> {
+    let grpc_req = RegisterRequest {
+        username: req.username,
+        password: req.password,
+        email: req.email,
+    };
+    let grpc_res = gateway.register(grpc_req).await?;
+    Ok(HttpRegisterResponse {
+        access_token: grpc_res.access_token,
+        refresh_token: grpc_res.refresh_token,
+        expires_at: grpc_res.expires_at,
+    })
+}
+
+async fn handle_validate(
+    gateway: Arc<GatewayServer>,
+    req: HttpValidateRequest,
+) -> HandlerResult<HttpValidateResponse> {
+    let grpc_req = ValidateRequest {
+        access_token: req.access_token,
+    };
+    let grpc_res = gateway.validate(grpc_req).await?;
+    Ok(HttpValidateResponse {
+        valid: grpc_res.valid,
+        user_id: grpc_res.user_id,
+        roles: grpc_res.roles,
+        expires_at: grpc_res.expires_at,
+    })
+}
+
+async fn handle_logout(
+    gateway: Arc<GatewayServer>,
+    req: HttpLogoutRequest,
+) -> HandlerResult<HttpLogoutResponse> {
+    let grpc_req = LogoutRequest {
+        username: req.username,
+        access_token: req.access_token,
+        all_sessions: req.all_sessions,
+    };
+    let grpc_res = gateway.logout(grpc_req).await?;
+    Ok(HttpLogoutResponse {
+        success: grpc_res.success,
+        message: grpc_res.message,
+    })
+}
+
+async fn handle_generate_tokens(
+    gateway: Arc<GatewayServer>,
+    req: HttpGenerateTokensRequest,
+) -> HandlerResult<HttpGenerateTokensResponse> {
+    let grpc_req = GenerateTokensRequest {
+        user_id: req.user_id,
+        roles: req.roles,
+    };
+    let grpc_res = gateway.generate_tokens(grpc_req).await?;
+    Ok(HttpGenerateTokensResponse {
+        access_token: grpc_res.access_token,
+        refresh_token: grpc_res.refresh_token,
+        expires_at: grpc_res.expires_at,
+    })
+}
+
+async fn handle_hello(
+    gateway: Arc<GatewayServer>,
+    identity: Identity,
+    req: HttpHelloRequest,
+) -> HandlerResult<HttpHelloResponse> {
+    let grpc_req = HelloRequest { name: req.name };
+    let grpc_res = gateway.say_hello(&identity, grpc_req).await?;
+    Ok(HttpHelloResponse {
+        message: grpc_res.message,
+    })
+}
+
+async fn handle_validate_cache_stats(gateway: Arc<GatewayServer>, _: ()) -> HandlerResult<ValidateCacheStats> {
+    Ok(gateway.validate_cache_stats())
+}
+
+async fn handle_client_certificate(
+    _: Arc<GatewayServer>,
+    identity: Identity,
+    _: (),
+) -> HandlerResult<HttpClientCertificateResponse> {
+    let certificate = identity
+        .client_certificate
+        .ok_or_else(|| GatewayError::UnauthenticatedError("client certificate required".to_string()))?;
+    Ok(HttpClientCertificateResponse {
+        subject: certificate.subject,
+        sans: certificate.sans,
+    })
+}
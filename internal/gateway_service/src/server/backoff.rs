@@ -0,0 +1,240 @@
+use futures::future::BoxFuture;
+use hyper_util::client::legacy::connect::HttpConnector;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tonic::transport::Uri;
+use tower::Service;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How upstream channels (re)connect: a bound on every attempt, and an exponential pause after
+/// each failed one during which calls fail right away instead of dialling again. Health checks
+/// end the pause early once the upstream accepts connections, see [`BackoffConnector::probe`].
+#[derive(Debug, Clone)]
+pub struct ConnectPolicy {
+    pub connect_timeout: Duration,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ConnectPolicy {
+    fn default() -> Self {
+        Self {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+}
+
+impl ConnectPolicy {
+    pub fn from_config(config: &config::UpstreamConnectConfig) -> Self {
+        let default = Self::default();
+        Self {
+            connect_timeout: config
+                .connect_timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(default.connect_timeout),
+            initial_backoff: config
+                .initial_backoff_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.initial_backoff),
+            max_backoff: config
+                .max_backoff_secs
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_backoff),
+        }
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = 1u32.checked_shl(failures.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+#[derive(Default)]
+struct BackoffState {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+/// TCP connector for one upstream that applies [`ConnectPolicy`]. TLS, when configured, is
+/// layered on top by tonic.
+#[derive(Clone)]
+pub struct BackoffConnector {
+    address: String,
+    http: HttpConnector,
+    policy: ConnectPolicy,
+    state: Arc<Mutex<BackoffState>>,
+}
+
+impl BackoffConnector {
+    pub fn new(address: String, policy: ConnectPolicy) -> Self {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_nodelay(true);
+        http.set_connect_timeout(Some(policy.connect_timeout));
+        Self {
+            address,
+            http,
+            policy,
+            state: Arc::default(),
+        }
+    }
+
+    /// Dials the upstream once, ignoring the backoff, and ends the backoff if it answers. Calls
+    /// would otherwise keep failing until `retry_at` even after the upstream is back; a failed
+    /// probe leaves the backoff as it is. Returns whether calls may dial the upstream now.
+    pub async fn probe(&self) -> bool {
+        let backing_off = self
+            .state
+            .lock()
+            .unwrap()
+            .retry_at
+            .is_some_and(|retry_at| retry_at > Instant::now());
+        if !backing_off {
+            return true;
+        }
+
+        let Ok(uri) = self.address.parse::<Uri>() else {
+            return false;
+        };
+        if self.http.clone().call(uri).await.is_err() {
+            return false;
+        }
+        log::info!("Upstream {} accepts connections again, ending its backoff", self.address);
+        *self.state.lock().unwrap() = BackoffState::default();
+        true
+    }
+}
+
+impl Service<Uri> for BackoffConnector {
+    type Response = <HttpConnector as Service<Uri>>::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        if let Some(retry_at) = self.state.lock().unwrap().retry_at {
+            let now = Instant::now();
+            if retry_at > now {
+                let error = format!(
+                    "{} is unreachable, next connect attempt in {}ms",
+                    self.address,
+                    (retry_at - now).as_millis()
+                );
+                return Box::pin(async move { Err(error.into()) });
+            }
+        }
+
+        let connecting = self.http.call(uri);
+        let address = self.address.clone();
+        let policy = self.policy.clone();
+        let state = self.state.clone();
+        Box::pin(async move {
+            let result = connecting.await;
+            let mut state = state.lock().unwrap();
+            match &result {
+                Ok(_) => {
+                    if state.failures > 0 {
+                        log::info!("Reconnected to upstream {}", address);
+                    }
+                    *state = BackoffState::default();
+                }
+                Err(e) => {
+                    state.failures += 1;
+                    let backoff = policy.backoff(state.failures);
+                    state.retry_at = Some(Instant::now() + backoff);
+                    log::warn!(
+                        "Connecting to upstream {} failed ({} in a row), backing off for {:?}: {}",
+                        address,
+                        state.failures,
+                        backoff,
+                        e
+                    );
+                }
+            }
+            result.map_err(Into::into)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_from_the_initial_delay_up_to_the_cap() {
+        let policy = ConnectPolicy {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        };
+
+        let cases = [
+            (0, 100),
+            (1, 100),
+            (2, 200),
+            (3, 400),
+            (5, 1600),
+            (6, 2000),
+            (10, 2000),
+            (32, 2000),
+            (u32::MAX, 2000),
+        ];
+        for (failures, millis) in cases {
+            assert_eq!(policy.backoff(failures), Duration::from_millis(millis), "failures = {}", failures);
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_fail_fast_while_backing_off() {
+        // Nothing listens on port 1, so the first attempt fails and starts the backoff
+        let policy = ConnectPolicy {
+            initial_backoff: Duration::from_secs(60),
+            ..ConnectPolicy::default()
+        };
+        let mut connector = BackoffConnector::new("http://127.0.0.1:1".to_string(), policy);
+        let uri: Uri = "http://127.0.0.1:1".parse().unwrap();
+
+        let first = connector.call(uri.clone()).await.err().unwrap();
+        assert!(!first.to_string().contains("next connect attempt"), "{}", first);
+
+        let second = connector.call(uri).await.err().unwrap();
+        assert!(second.to_string().contains("is unreachable, next connect attempt in"), "{}", second);
+        assert_eq!(connector.state.lock().unwrap().failures, 1);
+    }
+
+    #[tokio::test]
+    async fn probe_ends_the_backoff_once_the_upstream_is_back() {
+        // Reserve a free port, then close it so the first attempt is refused
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let policy = ConnectPolicy {
+            initial_backoff: Duration::from_secs(60),
+            ..ConnectPolicy::default()
+        };
+        let mut connector = BackoffConnector::new(address.clone(), policy);
+        let uri: Uri = address.parse().unwrap();
+
+        assert!(connector.call(uri.clone()).await.is_err());
+        assert!(!connector.probe().await, "nothing listens yet");
+        assert_eq!(connector.state.lock().unwrap().failures, 1);
+
+        let _listener = tokio::net::TcpListener::bind(address.trim_start_matches("http://")).await.unwrap();
+        assert!(connector.probe().await);
+        assert_eq!(connector.state.lock().unwrap().failures, 0);
+        assert!(connector.call(uri).await.is_ok());
+    }
+}
@@ -1,4 +1,6 @@
+pub mod backoff;
 pub mod service;
+pub mod validate_cache;
 pub mod generated {
     tonic::include_proto!("auth_service"); // это будет генерировать структуру для `AuthService` и всех его методов
 }
@@ -2,6 +2,9 @@ pub mod generated {
     tonic::include_proto!("auth_service");
 }
 
+use super::backoff::{BackoffConnector, ConnectPolicy};
+use super::validate_cache::{ValidateCache, ValidateCacheStats};
+use crate::errors::errors::GatewayError;
 pub use crate::auth::{
     auth_service_client::AuthServiceClient,
     GenerateTokensRequest, GenerateTokensResponse,
@@ -11,47 +14,249 @@ pub use crate::auth::{
     RegisterRequest, RegisterResponse,
     ValidateRequest, ValidateResponse,
 };
-use tonic::transport::Channel;
+pub use crate::hello::{hello_service_client::HelloServiceClient, HelloRequest, HelloResponse};
+use tonic::metadata::MetadataValue;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use std::sync::Arc;
 use tonic::Request;
 
+/// Metadata keys carrying the authenticated caller to upstream services. The gateway builds
+/// upstream requests itself, so clients cannot set these.
+pub const USER_ID_METADATA: &str = "x-user-id";
+pub const USER_ROLES_METADATA: &str = "x-user-roles";
+pub const CLIENT_CERT_SUBJECT_METADATA: &str = "x-client-cert-subject";
+pub const CLIENT_CERT_SAN_METADATA: &str = "x-client-cert-san";
+
+// Upper bound on streams the gateway opens on the upstream connection at once. Servers built on
+// hyper advertise 200 concurrent streams by default, and a client that opens thousands of streams
+// before it has seen that limit gets the connection torn down. Calls over the limit wait their
+// turn inside the channel instead.
+const UPSTREAM_CONCURRENCY_LIMIT: usize = 128;
+
+/// The caller of a request, as confirmed by `AuthService.Validate`.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub user_id: String,
+    pub roles: Vec<String>,
+    /// Set when the caller also presented a verified client certificate.
+    pub client_certificate: Option<PeerCertificate>,
+}
+
+/// The client certificate a caller presented and the listener verified during the handshake.
+/// Listeners attach it to every request of the connection as a request extension.
+#[derive(Debug, Clone)]
+pub struct PeerCertificate {
+    /// RFC 4514 distinguished name, e.g. `CN=billing,O=internal`.
+    pub subject: String,
+    /// Subject alternative names as `DNS:`, `URI:`, `email:` or `IP:` entries.
+    pub sans: Vec<String>,
+}
+
+impl PeerCertificate {
+    /// The metadata entries that carry the certificate to upstream services.
+    pub fn metadata(&self) -> Vec<(&'static str, String)> {
+        let mut metadata = vec![(CLIENT_CERT_SUBJECT_METADATA, self.subject.clone())];
+        if !self.sans.is_empty() {
+            metadata.push((CLIENT_CERT_SAN_METADATA, self.sans.join(",")));
+        }
+        metadata
+    }
+}
+
+impl Identity {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+/// A gRPC service the gateway holds a channel to, as seen by health checks.
+#[derive(Clone)]
+pub struct Upstream {
+    pub name: String,
+    pub address: String,
+    pub channel: Channel,
+    /// Shares its backoff with `channel`, so health checks can end it once the upstream is back.
+    pub connector: BackoffConnector,
+}
+
+impl Upstream {
+    /// A lazily connected channel to one upstream gRPC service, with TLS when `[upstream_tls]` is
+    /// configured.
+    pub fn connect(
+        name: String,
+        address: String,
+        tls: Option<&ClientTlsConfig>,
+        connect: &ConnectPolicy,
+    ) -> Result<Self, GatewayError> {
+        let connector = BackoffConnector::new(address.clone(), connect.clone());
+        // Also bounds the TLS handshake, which the connector's own timeout does not cover
+        let mut endpoint = Endpoint::from_shared(address.clone())?
+            .concurrency_limit(UPSTREAM_CONCURRENCY_LIMIT)
+            .connect_timeout(connect.connect_timeout);
+        if let Some(tls) = tls {
+            // tonic would silently fall back to plaintext for an http:// address
+            if endpoint.uri().scheme_str() != Some("https") {
+                return Err(GatewayError::ConfigError(format!(
+                    "upstream {} must be https:// when [upstream_tls] is configured",
+                    endpoint.uri()
+                )));
+            }
+            endpoint = endpoint.tls_config(tls.clone())?;
+        }
+        Ok(Self {
+            name,
+            address,
+            channel: endpoint.connect_with_connector_lazy(connector.clone()),
+            connector,
+        })
+    }
+}
+
+/// Thin wrapper over the upstream service clients.
+///
+/// Every call clones the client, which only clones the underlying `Channel` handle. Requests are
+/// multiplexed as separate HTTP/2 streams over the shared connection, so callers can share one
+/// `GatewayServer` behind an `Arc` and issue calls concurrently without any locking.
 #[derive(Clone)]
 pub struct GatewayServer {
     client: AuthServiceClient<Channel>,
+    hello: HelloServiceClient<Channel>,
+    auth_upstream: Upstream,
+    hello_upstream: Upstream,
+    validate_cache: Arc<ValidateCache>,
 }
 
 impl GatewayServer {
-    pub async fn new(auth_service_addr: String) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let client = AuthServiceClient::connect(auth_service_addr).await?;
-        Ok(Self { client })
+    /// Channels are dialled on first use, so the gateway starts even when the services are down;
+    /// calls made while one is unreachable fail with `UNAVAILABLE`.
+    pub fn new(
+        auth_service_addr: String,
+        hello_service_addr: String,
+        tls: Option<&ClientTlsConfig>,
+        connect: &ConnectPolicy,
+    ) -> Result<Self, GatewayError> {
+        let auth_upstream = Upstream::connect("auth_service".to_string(), auth_service_addr, tls, connect)?;
+        let hello_upstream = Upstream::connect("hello_service".to_string(), hello_service_addr, tls, connect)?;
+        Ok(Self {
+            client: AuthServiceClient::new(auth_upstream.channel.clone()),
+            hello: HelloServiceClient::new(hello_upstream.channel.clone()),
+            auth_upstream,
+            hello_upstream,
+            validate_cache: Arc::new(ValidateCache::default()),
+        })
+    }
+
+    pub fn with_validate_cache(mut self, cache: ValidateCache) -> Self {
+        self.validate_cache = Arc::new(cache);
+        self
+    }
+
+    /// The channel every authenticated request depends on.
+    pub fn auth_upstream(&self) -> &Upstream {
+        &self.auth_upstream
     }
 
-    pub async fn login(&mut self, req: LoginRequest) -> Result<LoginResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let response = self.client.login(Request::new(req)).await?.into_inner();
+    pub fn hello_upstream(&self) -> &Upstream {
+        &self.hello_upstream
+    }
+
+    pub fn validate_cache_stats(&self) -> ValidateCacheStats {
+        self.validate_cache.stats()
+    }
+
+    /// Resolves a bearer token to the caller's identity, failing for tokens that do not validate.
+    pub async fn authenticate(&self, access_token: &str) -> Result<Identity, GatewayError> {
+        let response = self
+            .validate(ValidateRequest {
+                access_token: access_token.to_string(),
+            })
+            .await?;
+        if !response.valid {
+            return Err(GatewayError::UnauthenticatedError("invalid or expired access token".to_string()));
+        }
+        Ok(Identity {
+            user_id: response.user_id,
+            roles: response.roles,
+            client_certificate: None,
+        })
+    }
+
+    pub async fn login(&self, req: LoginRequest) -> Result<LoginResponse, GatewayError> {
+        let response = self.client.clone().login(Request::new(req)).await?.into_inner();
         Ok(response)
     }
 
-    pub async fn refresh(&mut self, req: RefreshRequest) -> Result<RefreshResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let response = self.client.refresh(Request::new(req)).await?.into_inner();
+    pub async fn refresh(&self, req: RefreshRequest) -> Result<RefreshResponse, GatewayError> {
+        let response = self.client.clone().refresh(Request::new(req)).await?.into_inner();
         Ok(response)
     }
 
-    pub async fn validate(&mut self, req: ValidateRequest) -> Result<ValidateResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let response = self.client.validate(Request::new(req)).await?.into_inner();
+    /// Answers from the validate cache when possible and fills it from `AuthService` otherwise.
+    pub async fn validate(&self, req: ValidateRequest) -> Result<ValidateResponse, GatewayError> {
+        if let Some(cached) = self.validate_cache.get(&req.access_token) {
+            return Ok(cached);
+        }
+
+        let token = req.access_token.clone();
+        let response = self.client.clone().validate(Request::new(req)).await?.into_inner();
+        self.validate_cache.insert(&token, &response);
         Ok(response)
     }
 
-    pub async fn logout(&mut self, req: LogoutRequest) -> Result<LogoutResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let response = self.client.logout(Request::new(req)).await?.into_inner();
+    pub async fn logout(&self, req: LogoutRequest) -> Result<LogoutResponse, GatewayError> {
+        let token = req.access_token.clone();
+        let response = self.client.clone().logout(Request::new(req)).await?.into_inner();
+        // Other tokens revoked by an all-sessions logout stay cached until their entries expire
+        self.validate_cache.remove(&token);
         Ok(response)
     }
 
-    pub async fn register(&mut self, req: RegisterRequest) -> Result<RegisterResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let response = self.client.register(Request::new(req)).await?.into_inner();
+    pub async fn register(&self, req: RegisterRequest) -> Result<RegisterResponse, GatewayError> {
+        let response = self.client.clone().register(Request::new(req)).await?.into_inner();
         Ok(response)
     }
 
-    pub async fn generate_tokens(&mut self, req: GenerateTokensRequest) -> Result<GenerateTokensResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let response = self.client.generate_tokens(Request::new(req)).await?.into_inner();
+    pub async fn generate_tokens(&self, req: GenerateTokensRequest) -> Result<GenerateTokensResponse, GatewayError> {
+        let response = self.client.clone().generate_tokens(Request::new(req)).await?.into_inner();
         Ok(response)
     }
-}
\ No newline at end of file
+
+    pub async fn say_hello(&self, identity: &Identity, req: HelloRequest) -> Result<HelloResponse, GatewayError> {
+        let request = with_identity(Request::new(req), identity)?;
+        let response = self.hello.clone().say_hello(request).await?.into_inner();
+        Ok(response)
+    }
+}
+
+fn with_identity<T>(mut request: Request<T>, identity: &Identity) -> Result<Request<T>, GatewayError> {
+    let invalid = |_| GatewayError::InternalError("identity is not valid metadata".to_string());
+    let user_id: MetadataValue<_> = identity.user_id.parse().map_err(invalid)?;
+    let roles: MetadataValue<_> = identity.roles.join(",").parse().map_err(invalid)?;
+
+    let metadata = request.metadata_mut();
+    metadata.insert(USER_ID_METADATA, user_id);
+    metadata.insert(USER_ROLES_METADATA, roles);
+    for (key, value) in identity.client_certificate.iter().flat_map(PeerCertificate::metadata) {
+        metadata.insert(key, value.parse().map_err(invalid)?);
+    }
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn upstream_tls_refuses_plaintext_addresses() {
+        // Other tests may have installed it already
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let tls = ClientTlsConfig::new();
+        let connect = ConnectPolicy::default();
+
+        let upstream = |address: &str, tls| Upstream::connect("auth".to_string(), address.to_string(), tls, &connect);
+
+        assert!(matches!(upstream("http://127.0.0.1:50051", Some(&tls)), Err(GatewayError::ConfigError(_))));
+        assert!(upstream("https://127.0.0.1:50051", Some(&tls)).is_ok());
+        assert!(upstream("http://127.0.0.1:50051", None).is_ok());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::auth::ValidateResponse;
+use lru::LruCache;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+const DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(5);
+
+type TokenHash = [u8; 32];
+
+struct Entry {
+    response: ValidateResponse,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidateCacheStats {
+    pub hits: u64,
+    pub negative_hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+/// In-process LRU of `AuthService.Validate` answers, keyed by a SHA-256 of the token so raw
+/// tokens are never kept in memory.
+///
+/// A cached positive answer can outlive a revocation made elsewhere for up to `ttl`, which is
+/// the price of skipping the upstream round-trip. Rejected tokens are cached for `negative_ttl`
+/// so a client retrying a bad token does not reach `AuthService` on every request.
+pub struct ValidateCache {
+    entries: Mutex<LruCache<TokenHash, Entry>>,
+    ttl: Duration,
+    negative_ttl: Duration,
+    hits: AtomicU64,
+    negative_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Default for ValidateCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, DEFAULT_TTL, DEFAULT_NEGATIVE_TTL)
+    }
+}
+
+impl ValidateCache {
+    pub fn new(capacity: usize, ttl: Duration, negative_ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            ttl,
+            negative_ttl,
+            hits: AtomicU64::new(0),
+            negative_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn from_config(config: &config::ValidateCacheConfig) -> Self {
+        Self::new(
+            config.capacity.unwrap_or(DEFAULT_CAPACITY),
+            config.ttl_secs.map(Duration::from_secs).unwrap_or(DEFAULT_TTL),
+            config
+                .negative_ttl_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_NEGATIVE_TTL),
+        )
+    }
+
+    pub fn get(&self, token: &str) -> Option<ValidateResponse> {
+        let key = hash(token);
+        let mut entries = self.entries.lock().unwrap();
+
+        let cached = match entries.get(&key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.response.clone()),
+            Some(_) => {
+                entries.pop(&key);
+                None
+            }
+            None => None,
+        };
+        drop(entries);
+
+        match &cached {
+            Some(response) if response.valid => self.hits.fetch_add(1, Ordering::Relaxed),
+            Some(_) => self.negative_hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        cached
+    }
+
+    /// Caches an answer until `min(token expiry, ttl)`, or for `negative_ttl` if it was a rejection.
+    pub fn insert(&self, token: &str, response: &ValidateResponse) {
+        let Some(lifetime) = self.lifetime(response, unix_now()) else {
+            return;
+        };
+
+        self.entries.lock().unwrap().put(
+            hash(token),
+            Entry {
+                response: response.clone(),
+                expires_at: Instant::now() + lifetime,
+            },
+        );
+    }
+
+    fn lifetime(&self, response: &ValidateResponse, now: i64) -> Option<Duration> {
+        let lifetime = if response.valid {
+            let remaining = response.expires_at - now;
+            if remaining <= 0 {
+                return None;
+            }
+            self.ttl.min(Duration::from_secs(remaining as u64))
+        } else {
+            self.negative_ttl
+        };
+        (!lifetime.is_zero()).then_some(lifetime)
+    }
+
+    pub fn remove(&self, token: &str) {
+        self.entries.lock().unwrap().pop(&hash(token));
+    }
+
+    pub fn stats(&self) -> ValidateCacheStats {
+        let entries = self.entries.lock().unwrap();
+        ValidateCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            negative_hits: self.negative_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: entries.len(),
+            capacity: entries.cap().get(),
+        }
+    }
+}
+
+fn hash(token: &str) -> TokenHash {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_until(expires_at: i64) -> ValidateResponse {
+        ValidateResponse {
+            valid: true,
+            user_id: "user-1".to_string(),
+            roles: vec!["user".to_string()],
+            expires_at,
+        }
+    }
+
+    fn rejected() -> ValidateResponse {
+        ValidateResponse {
+            valid: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn positive_lifetime_is_bounded_by_ttl_and_token_expiry() {
+        let cache = ValidateCache::new(16, Duration::from_secs(30), Duration::from_secs(5));
+        let now = 1_000;
+
+        let cases = [
+            (now + 3600, Some(Duration::from_secs(30))),
+            (now + 10, Some(Duration::from_secs(10))),
+            (now + 30, Some(Duration::from_secs(30))),
+            (now, None),
+            (now - 1, None),
+        ];
+        for (expires_at, expected) in cases {
+            assert_eq!(cache.lifetime(&valid_until(expires_at), now), expected, "expires_at = {}", expires_at);
+        }
+    }
+
+    #[test]
+    fn rejections_live_for_negative_ttl() {
+        let cache = ValidateCache::new(16, Duration::from_secs(30), Duration::from_secs(5));
+        assert_eq!(cache.lifetime(&rejected(), 1_000), Some(Duration::from_secs(5)));
+
+        let disabled = ValidateCache::new(16, Duration::from_secs(30), Duration::ZERO);
+        assert_eq!(disabled.lifetime(&rejected(), 1_000), None);
+    }
+
+    #[test]
+    fn expired_token_is_not_cached() {
+        let cache = ValidateCache::default();
+        cache.insert("token", &valid_until(unix_now() - 1));
+
+        assert!(cache.get("token").is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn rejected_token_is_served_from_cache_until_negative_ttl() {
+        let cache = ValidateCache::new(16, Duration::from_secs(30), Duration::from_millis(50));
+        cache.insert("bad", &rejected());
+
+        assert_eq!(cache.get("bad").map(|response| response.valid), Some(false));
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(cache.get("bad").is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.negative_hits, stats.misses), (0, 1, 1));
+        assert_eq!(stats.entries, 0);
+    }
+
+    #[test]
+    fn valid_token_is_served_from_cache() {
+        let cache = ValidateCache::default();
+        cache.insert("good", &valid_until(unix_now() + 600));
+
+        assert_eq!(cache.get("good").map(|response| response.user_id), Some("user-1".to_string()));
+        cache.remove("good");
+        assert!(cache.get("good").is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.negative_hits, stats.misses), (1, 0, 1));
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+
+// Long enough for load balancers to see the failing readiness probe
+const DEFAULT_DRAIN_PERIOD: Duration = Duration::from_secs(5);
+// Together with the drain period, leaves headroom under the 30 s Kubernetes gives a pod
+// between SIGTERM and SIGKILL
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Shared by the listeners: once triggered they stop accepting, ask clients to go away and
+/// give in-flight requests up to `drain_timeout` to finish.
+#[derive(Clone)]
+pub struct Shutdown {
+    token: CancellationToken,
+    drain_period: Duration,
+    drain_timeout: Duration,
+}
+
+impl Shutdown {
+    pub fn new(config: Option<&config::ShutdownConfig>) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            drain_period: config
+                .and_then(|config| config.drain_period_secs)
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_DRAIN_PERIOD),
+            drain_timeout: config
+                .and_then(|config| config.drain_timeout_secs)
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_DRAIN_TIMEOUT),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    pub async fn triggered(&self) {
+        self.token.cancelled().await
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        self.drain_timeout
+    }
+
+    /// On SIGTERM, which is what orchestrators send, or on Ctrl-C: calls `on_drain` so the
+    /// gateway reports not ready, keeps serving for `drain_period` while load balancers take it
+    /// out of rotation, then triggers the shutdown.
+    pub fn trigger_on_signal(&self, on_drain: impl FnOnce() + Send + 'static) -> std::io::Result<()> {
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = sigterm.recv() => log::info!("SIGTERM received"),
+                _ = tokio::signal::ctrl_c() => log::info!("Ctrl-C received"),
+            }
+            on_drain();
+            log::info!("Reporting not ready, closing listeners in {:?}", shutdown.drain_period);
+            tokio::time::sleep(shutdown.drain_period).await;
+
+            log::info!("Shutting down, draining connections for up to {:?}", shutdown.drain_timeout);
+            shutdown.trigger();
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use super::identity::TlsError;
+use super::pem::read_certificates;
+use config::ClientAuth;
+use rustls::server::danger::ClientCertVerifier;
+use rustls::server::WebPkiClientVerifier;
+use rustls::RootCertStore;
+use std::sync::Arc;
+
+/// Builds the client certificate verifier for `[tls].client_ca_path`.
+///
+/// In `optional` mode clients without a certificate are still accepted, but one that is sent
+/// must chain to the CA bundle.
+pub fn client_verifier(ca_path: &str, mode: ClientAuth) -> Result<Arc<dyn ClientCertVerifier>, TlsError> {
+    let required = mode == ClientAuth::Required;
+
+    let mut roots = RootCertStore::empty();
+    for cert in read_certificates(ca_path)? {
+        roots.add(cert)?;
+    }
+
+    log::info!(
+        "Client certificates {} and verified against {}",
+        if required { "required" } else { "accepted" },
+        ca_path
+    );
+    let builder = WebPkiClientVerifier::builder(Arc::new(roots));
+    let builder = if required { builder } else { builder.allow_unauthenticated() };
+    Ok(builder.build()?)
+}
@@ -0,0 +1,159 @@
+use super::pem::{read_certificates, read_private_key};
+use super::resolver::CertResolver;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::sign::CertifiedKey;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TlsError {
+    #[error("Failed to open {path}: {source}")]
+    Open { path: String, source: std::io::Error },
+
+    #[error("Failed to parse {path}: {reason}")]
+    Parse { path: String, reason: String },
+
+    #[error("Failed to parse PEM block {block} ({label}) of {path}: {reason}")]
+    Block {
+        path: String,
+        block: usize,
+        label: String,
+        reason: String,
+    },
+
+    #[error("Environment variable {0} named by [tls].key_passphrase_env is not set")]
+    MissingPassphrase(String),
+
+    #[error("No TLS certificate configured; set [tls].cert_path and [tls].key_path or enable [tls].dev_self_signed")]
+    NotConfigured,
+
+    #[error("Failed to generate self-signed certificate: {0}")]
+    SelfSigned(#[from] rcgen::Error),
+
+    #[error("Failed to create TLS config: {0}")]
+    Rustls(#[from] rustls::Error),
+
+    #[error("Failed to create client certificate verifier: {0}")]
+    ClientVerifier(#[from] rustls::server::VerifierBuilderError),
+
+    #[error("No rustls crypto provider installed")]
+    NoCryptoProvider,
+}
+
+/// Where an identity is read from. The leaf certificate file may already carry the
+/// intermediates; `chain_path` is for deployments that ship them separately.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IdentityFiles {
+    pub cert_path: String,
+    pub chain_path: Option<String>,
+    pub key_path: String,
+}
+
+impl IdentityFiles {
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        [Some(&self.cert_path), self.chain_path.as_ref(), Some(&self.key_path)]
+            .into_iter()
+            .flatten()
+            .map(Path::new)
+    }
+}
+
+/// A certificate chain and its private key, shared by every listener that serves it.
+#[derive(Debug)]
+pub struct TlsIdentity {
+    pub cert_chain: Vec<CertificateDer<'static>>,
+    pub key: PrivateKeyDer<'static>,
+}
+
+impl TlsIdentity {
+    pub fn load(files: &IdentityFiles, key_passphrase: Option<&str>) -> Result<Self, TlsError> {
+        let mut cert_chain = read_certificates(&files.cert_path)?;
+        if let Some(chain_path) = &files.chain_path {
+            cert_chain.extend(read_certificates(chain_path)?);
+        }
+
+        Ok(Self {
+            cert_chain,
+            key: read_private_key(&files.key_path, key_passphrase)?,
+        })
+    }
+
+    /// A throwaway certificate for `localhost`. Clients will not trust it.
+    pub fn self_signed() -> Result<Self, TlsError> {
+        let cert_key = rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
+        Ok(Self {
+            cert_chain: vec![CertificateDer::from(cert_key.cert.der().to_vec())],
+            key: PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert_key.key_pair.serialized_der().to_vec())),
+        })
+    }
+
+    /// Parses the key and checks that it matches the leaf certificate.
+    pub fn certified_key(self) -> Result<CertifiedKey, TlsError> {
+        let provider = CryptoProvider::get_default().ok_or(TlsError::NoCryptoProvider)?;
+        Ok(CertifiedKey::from_der(self.cert_chain, self.key, provider)?)
+    }
+}
+
+/// Loads each certificate/key pair once, so listeners pointing at the same files (or both
+/// falling back to the dev certificate) present the same identity and reload it together.
+pub struct IdentityLoader {
+    dev_self_signed: bool,
+    key_passphrase: Option<String>,
+    loaded: Mutex<HashMap<IdentityFiles, Arc<CertResolver>>>,
+    self_signed: Mutex<Option<Arc<CertResolver>>>,
+}
+
+impl IdentityLoader {
+    /// `key_passphrase_env` names the environment variable holding the passphrase of
+    /// encrypted keys; the passphrase itself never goes into the config file.
+    pub fn new(dev_self_signed: bool, key_passphrase_env: Option<&str>) -> Result<Self, TlsError> {
+        let key_passphrase = match key_passphrase_env {
+            Some(name) => Some(std::env::var(name).map_err(|_| TlsError::MissingPassphrase(name.to_string()))?),
+            None => None,
+        };
+        Ok(Self {
+            dev_self_signed,
+            key_passphrase,
+            loaded: Mutex::new(HashMap::new()),
+            self_signed: Mutex::new(None),
+        })
+    }
+
+    pub fn load(&self, files: Option<IdentityFiles>) -> Result<Arc<CertResolver>, TlsError> {
+        let Some(files) = files else {
+            return self.self_signed();
+        };
+
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some(identity) = loaded.get(&files) {
+            return Ok(identity.clone());
+        }
+        let resolver = Arc::new(CertResolver::from_files(files.clone(), self.key_passphrase.clone())?);
+        log::info!("Loaded TLS certificate from {}", files.cert_path);
+        loaded.insert(files, resolver.clone());
+        Ok(resolver)
+    }
+
+    /// Every identity backed by files, i.e. the ones that can be reloaded.
+    pub fn file_backed(&self) -> Vec<Arc<CertResolver>> {
+        self.loaded.lock().unwrap().values().cloned().collect()
+    }
+
+    fn self_signed(&self) -> Result<Arc<CertResolver>, TlsError> {
+        if !self.dev_self_signed {
+            return Err(TlsError::NotConfigured);
+        }
+
+        let mut self_signed = self.self_signed.lock().unwrap();
+        if let Some(identity) = self_signed.as_ref() {
+            return Ok(identity.clone());
+        }
+        log::warn!("No TLS certificate configured, serving a self-signed certificate for localhost (dev mode only)");
+        let resolver = Arc::new(CertResolver::fixed(TlsIdentity::self_signed()?)?);
+        *self_signed = Some(resolver.clone());
+        Ok(resolver)
+    }
+}
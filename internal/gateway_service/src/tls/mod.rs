@@ -0,0 +1,7 @@
+pub mod client_auth;
+pub mod identity;
+pub mod peer;
+pub mod pem;
+pub mod reload;
+pub mod resolver;
+pub mod upstream;
@@ -0,0 +1,39 @@
+use crate::server::service::PeerCertificate;
+use rustls::pki_types::CertificateDer;
+use std::net::IpAddr;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// Reads the leaf of a verified client chain. `None` if the client sent no certificate.
+pub fn peer_certificate(chain: Option<&[CertificateDer<'_>]>) -> Option<PeerCertificate> {
+    let leaf = chain?.first()?;
+    let (_, cert) = X509Certificate::from_der(leaf.as_ref())
+        .map_err(|e| log::error!("Failed to parse client certificate: {}", e))
+        .ok()?;
+
+    let sans = match cert.subject_alternative_name() {
+        Ok(Some(extension)) => extension.value.general_names.iter().filter_map(format_name).collect(),
+        _ => Vec::new(),
+    };
+    Some(PeerCertificate {
+        subject: cert.subject().to_string(),
+        sans,
+    })
+}
+
+fn format_name(name: &GeneralName<'_>) -> Option<String> {
+    match name {
+        GeneralName::DNSName(dns) => Some(format!("DNS:{}", dns)),
+        GeneralName::URI(uri) => Some(format!("URI:{}", uri)),
+        GeneralName::RFC822Name(email) => Some(format!("email:{}", email)),
+        GeneralName::IPAddress(bytes) => {
+            let ip = match bytes.len() {
+                4 => IpAddr::from(<[u8; 4]>::try_from(*bytes).ok()?),
+                16 => IpAddr::from(<[u8; 16]>::try_from(*bytes).ok()?),
+                _ => return None,
+            };
+            Some(format!("IP:{}", ip))
+        }
+        _ => None,
+    }
+}
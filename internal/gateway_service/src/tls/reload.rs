@@ -0,0 +1,143 @@
+use super::identity::IdentityFiles;
+use super::resolver::CertResolver;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+
+// Cert and key are usually replaced one after the other; wait for both before reloading
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Reloads every file-backed identity on SIGHUP and, if `watch_files` is set, whenever anything
+/// changes in the directory of its certificate or key.
+pub fn spawn_reloader(resolvers: Vec<Arc<CertResolver>>, watch_files: bool) -> anyhow::Result<()> {
+    if resolvers.is_empty() {
+        return Ok(());
+    }
+
+    let mut sighup = signal(SignalKind::hangup())?;
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<PathBuf>>();
+
+    // The parent directories are watched rather than the files, so renames over them are seen
+    let watcher = if watch_files {
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                // Reading the files on reload must not trigger another reload
+                Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+                Ok(event) => {
+                    let _ = tx.send(event.paths);
+                }
+                Err(e) => log::error!("TLS certificate watch error: {}", e),
+            }
+        })?;
+        let directories: HashSet<PathBuf> = resolvers
+            .iter()
+            .filter_map(|resolver| resolver.files())
+            .flat_map(IdentityFiles::paths)
+            .map(parent_directory)
+            .collect::<Result<_, _>>()?;
+        for directory in &directories {
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+            log::info!("Watching {} for TLS certificate changes", directory.display());
+        }
+        Some(watcher)
+    } else {
+        drop(tx);
+        None
+    };
+
+    tokio::spawn(async move {
+        // Dropping the watcher would stop the notifications
+        let _watcher = watcher;
+        loop {
+            let changed = tokio::select! {
+                _ = sighup.recv() => {
+                    log::info!("SIGHUP received, reloading TLS certificates");
+                    None
+                }
+                Some(paths) = rx.recv() => {
+                    tokio::time::sleep(DEBOUNCE).await;
+                    let mut changed: HashSet<PathBuf> = paths.into_iter().collect();
+                    while let Ok(paths) = rx.try_recv() {
+                        changed.extend(paths);
+                    }
+                    Some(changed)
+                }
+            };
+
+            for resolver in &resolvers {
+                if let (Some(changed), Some(files)) = (&changed, resolver.files()) {
+                    if !watches_any(files, changed) {
+                        continue;
+                    }
+                }
+                reload(resolver);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn reload(resolver: &CertResolver) {
+    let Some(files) = resolver.files() else {
+        return;
+    };
+    match resolver.reload() {
+        Ok(()) => log::info!("Reloaded TLS certificate from {}", files.cert_path),
+        Err(e) => log::error!(
+            "Failed to reload TLS certificate from {}, keeping the current one: {}",
+            files.cert_path,
+            e
+        ),
+    }
+}
+
+/// Matches on the directory rather than the file: when the files are symlinks, as in a mounted
+/// Kubernetes secret, an update swaps a `..data` link next to them and the files themselves see
+/// no event.
+fn watches_any(files: &IdentityFiles, changed: &HashSet<PathBuf>) -> bool {
+    files
+        .paths()
+        .filter_map(|path| parent_directory(path).ok())
+        .any(|directory| changed.iter().any(|path| path.parent() == Some(directory.as_path())))
+}
+
+fn parent_directory(path: &Path) -> std::io::Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    Ok(path.parent().map(Path::to_path_buf).unwrap_or(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> IdentityFiles {
+        IdentityFiles {
+            cert_path: "/etc/gateway/tls/tls.crt".to_string(),
+            chain_path: None,
+            key_path: "/etc/gateway/keys/tls.key".to_string(),
+        }
+    }
+
+    #[test]
+    fn any_change_in_a_watched_directory_triggers_a_reload() {
+        let cases = [
+            ("/etc/gateway/tls/tls.crt", true),
+            ("/etc/gateway/keys/tls.key", true),
+            ("/etc/gateway/tls/..data", true),
+            ("/etc/gateway/tls/..data_tmp", true),
+            ("/etc/gateway/keys/..2026_10_18_12_00_00.123456789", true),
+            ("/etc/gateway/tls/..data/tls.crt", false),
+            ("/etc/gateway/other.crt", false),
+            ("/var/lib/tls/tls.crt", false),
+        ];
+        for (path, expected) in cases {
+            let changed = HashSet::from([PathBuf::from(path)]);
+            assert_eq!(watches_any(&files(), &changed), expected, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use super::identity::{IdentityFiles, TlsError, TlsIdentity};
+use arc_swap::ArcSwap;
+use rustls::server::danger::ClientCertVerifier;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+use std::sync::Arc;
+
+/// Serves the current certificate to new handshakes and lets it be replaced while the
+/// listeners keep running. Established connections keep the identity they negotiated.
+#[derive(Debug)]
+pub struct CertResolver {
+    files: Option<IdentityFiles>,
+    key_passphrase: Option<String>,
+    current: ArcSwap<CertifiedKey>,
+}
+
+impl CertResolver {
+    pub fn from_files(files: IdentityFiles, key_passphrase: Option<String>) -> Result<Self, TlsError> {
+        let certified_key = load(&files, key_passphrase.as_deref())?;
+        Ok(Self {
+            files: Some(files),
+            key_passphrase,
+            current: ArcSwap::from_pointee(certified_key),
+        })
+    }
+
+    /// An identity that did not come from files and is never reloaded.
+    pub fn fixed(identity: TlsIdentity) -> Result<Self, TlsError> {
+        Ok(Self {
+            files: None,
+            key_passphrase: None,
+            current: ArcSwap::from_pointee(identity.certified_key()?),
+        })
+    }
+
+    pub fn files(&self) -> Option<&IdentityFiles> {
+        self.files.as_ref()
+    }
+
+    /// Re-reads the certificate and key. On any error the current identity stays in place.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let Some(files) = &self.files else {
+            return Ok(());
+        };
+        self.current.store(Arc::new(load(files, self.key_passphrase.as_deref())?));
+        Ok(())
+    }
+
+    pub fn server_config(
+        self: &Arc<Self>,
+        alpn: Vec<Vec<u8>>,
+        client_verifier: Option<Arc<dyn ClientCertVerifier>>,
+    ) -> ServerConfig {
+        let builder = ServerConfig::builder();
+        let builder = match client_verifier {
+            Some(verifier) => builder.with_client_cert_verifier(verifier),
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_cert_resolver(self.clone());
+        config.alpn_protocols = alpn;
+        config
+    }
+}
+
+fn load(files: &IdentityFiles, key_passphrase: Option<&str>) -> Result<CertifiedKey, TlsError> {
+    TlsIdentity::load(files, key_passphrase)?
+        .certified_key()
+        .map_err(|e| match e {
+            TlsError::Rustls(e) => TlsError::Parse {
+                path: files.key_path.clone(),
+                reason: format!("not usable with the certificate in {}: {}", files.cert_path, e),
+            },
+            other => other,
+        })
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.load_full())
+    }
+}
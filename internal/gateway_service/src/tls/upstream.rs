@@ -0,0 +1,34 @@
+use super::identity::TlsError;
+use config::UpstreamTlsConfig;
+use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+
+/// Builds the TLS settings for the gateway's gRPC channels from `[upstream_tls]`.
+///
+/// The system's trusted roots are not consulted, so upstream certificates must chain to the
+/// required `ca_path`. A client certificate turns the connection into mTLS.
+pub fn client_tls_config(config: &UpstreamTlsConfig) -> Result<ClientTlsConfig, TlsError> {
+    let mut tls = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(read(&config.ca_path)?));
+    match (&config.cert_path, &config.key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            tls = tls.identity(Identity::from_pem(read(cert_path)?, read(key_path)?));
+        }
+        (None, None) => {}
+        _ => {
+            return Err(TlsError::Parse {
+                path: "[upstream_tls]".to_string(),
+                reason: "cert_path and key_path must be set together".to_string(),
+            })
+        }
+    }
+    if let Some(domain) = &config.domain {
+        tls = tls.domain_name(domain.clone());
+    }
+    Ok(tls)
+}
+
+fn read(path: &str) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|source| TlsError::Open {
+        path: path.to_string(),
+        source,
+    })
+}
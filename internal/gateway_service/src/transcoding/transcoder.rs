@@ -0,0 +1,557 @@
+use crate::errors::errors::GatewayError;
+use crate::proxy::grpc_proxy::GrpcProxy;
+use crate::router::router::{HandlerResult, REQUEST_ID_HEADER};
+use crate::server::service::PeerCertificate;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use config::{TranscodedRouteConfig, TranscodingConfig};
+use http_body_util::{BodyExt, Full};
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use hyper::{HeaderMap, Method, Request, Response, StatusCode};
+use percent_encoding::percent_decode_str;
+use prost_reflect::prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor, Value};
+use serde_json::{Map, Value as Json};
+use std::str::FromStr;
+use std::sync::Arc;
+use tonic::Status;
+
+// Descriptors of the protos compiled by build.rs
+const BUILTIN_DESCRIPTORS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/gateway_descriptor.bin"));
+
+const HTTP_RULE_EXTENSION: &str = "google.api.http";
+const HTTP_RULE_METHODS: [(&str, Method); 5] = [
+    ("get", Method::GET),
+    ("put", Method::PUT),
+    ("post", Method::POST),
+    ("delete", Method::DELETE),
+    ("patch", Method::PATCH),
+];
+
+enum Segment {
+    Literal(String),
+    Field(String),
+}
+
+/// An HTTP method and path template bound to a gRPC method.
+struct TranscodedRoute {
+    method: Method,
+    template: Vec<Segment>,
+    grpc_method: MethodDescriptor,
+    /// `None` when the request message is built from the path alone, `"*"` when the whole JSON
+    /// body is the request message, otherwise the request field the body is decoded into.
+    body: Option<String>,
+}
+
+/// Turns JSON requests into protobuf using descriptors loaded at startup, calls the method
+/// through the gRPC proxy and renders the reply with the proto3 JSON mapping.
+///
+/// Calls go through [`GrpcProxy`], so the upstream and access rules of a method are the ones
+/// configured for it in `[[grpc_routes]]`.
+#[derive(Clone)]
+pub struct Transcoder {
+    routes: Arc<Vec<TranscodedRoute>>,
+    proxy: GrpcProxy,
+}
+
+impl Transcoder {
+    pub fn new(config: &TranscodingConfig, proxy: GrpcProxy) -> Result<Self, GatewayError> {
+        let mut pool = DescriptorPool::decode(BUILTIN_DESCRIPTORS)
+            .map_err(|e| GatewayError::ConfigError(format!("embedded descriptors: {}", e)))?;
+        for path in &config.descriptor_sets {
+            let bytes = std::fs::read(path)
+                .map_err(|e| GatewayError::ConfigError(format!("failed to read {}: {}", path, e)))?;
+            pool.decode_file_descriptor_set(bytes.as_slice())
+                .map_err(|e| GatewayError::ConfigError(format!("invalid descriptor set {}: {}", path, e)))?;
+        }
+
+        let mut routes = annotated_routes(&pool)?;
+        for route_config in &config.routes {
+            routes.push(configured_route(&pool, route_config)?);
+        }
+
+        for route in &routes {
+            let grpc_path = grpc_path(&route.grpc_method);
+            if !proxy.has_route(&grpc_path) {
+                log::warn!("Transcoded method {} has no [[grpc_routes]] upstream", grpc_path);
+            }
+            log::info!("Transcoding {} {} to {}", route.method, template_string(&route.template), grpc_path);
+        }
+
+        Ok(Self {
+            routes: Arc::new(routes),
+            proxy,
+        })
+    }
+
+    /// Whether any transcoded route has this path, whatever its method.
+    pub fn has_path(&self, path: &str) -> bool {
+        self.routes.iter().any(|route| match_template(&route.template, path).is_some())
+    }
+
+    /// Transcodes and forwards the request, or returns `None` if no route matches it.
+    pub async fn handle(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        peer: Option<&PeerCertificate>,
+        body: Bytes,
+    ) -> Option<HandlerResult<Response<Bytes>>> {
+        let (route, bindings) = self.routes.iter().find_map(|route| {
+            if route.method != *method {
+                return None;
+            }
+            match_template(&route.template, path).map(|bindings| (route, bindings))
+        })?;
+
+        Some(self.call(route, bindings, headers, peer, body).await)
+    }
+
+    async fn call(
+        &self,
+        route: &TranscodedRoute,
+        bindings: Vec<(String, String)>,
+        headers: &HeaderMap,
+        peer: Option<&PeerCertificate>,
+        body: Bytes,
+    ) -> HandlerResult<Response<Bytes>> {
+        let request = build_request(route, bindings, &body)?;
+
+        let mut frame = BytesMut::with_capacity(5 + request.encoded_len());
+        frame.put_u8(0);
+        frame.put_u32(request.encoded_len() as u32);
+        request.encode(&mut frame).map_err(|e| GatewayError::InternalError(e.to_string()))?;
+
+        let mut grpc_req = Request::builder()
+            .method(Method::POST)
+            .uri(grpc_path(&route.grpc_method))
+            .header(CONTENT_TYPE, "application/grpc")
+            .header("te", "trailers");
+        for name in [AUTHORIZATION.as_str(), REQUEST_ID_HEADER] {
+            if let Some(value) = headers.get(name) {
+                grpc_req = grpc_req.header(name, value);
+            }
+        }
+        let body = Full::new(frame.freeze()).map_err(|never| match never {}).boxed_unsync();
+        let mut grpc_req = grpc_req.body(body).map_err(|e| GatewayError::InternalError(e.to_string()))?;
+        if let Some(peer) = peer {
+            grpc_req.extensions_mut().insert(peer.clone());
+        }
+
+        let response = self.proxy.forward(grpc_req).await;
+        let (parts, body) = response.into_parts();
+        let collected = body.collect().await.map_err(GatewayError::from)?;
+
+        // A failed call may come back trailers-only, with the status in the headers
+        let status = collected
+            .trailers()
+            .and_then(Status::from_header_map)
+            .or_else(|| Status::from_header_map(&parts.headers));
+        match status {
+            Some(status) if status.code() != tonic::Code::Ok => return Err(status.into()),
+            None if parts.status != StatusCode::OK => {
+                return Err(GatewayError::InternalError(format!("upstream answered {}", parts.status)))
+            }
+            _ => {}
+        }
+
+        let reply = decode_reply(route, collected.to_bytes())?;
+        let json = serde_json::to_vec(&reply).map_err(|e| GatewayError::InternalError(e.to_string()))?;
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .body(Bytes::from(json))
+            .map_err(|e| GatewayError::InternalError(e.to_string()))
+    }
+}
+
+/// Builds the request message from the JSON body and the path variables. Path variables are
+/// set last, so they win over the same fields in the body.
+fn build_request(
+    route: &TranscodedRoute,
+    bindings: Vec<(String, String)>,
+    body: &Bytes,
+) -> HandlerResult<DynamicMessage> {
+    let invalid = |e: serde_json::Error| GatewayError::InvalidRequestError(format!("Invalid JSON: {}", e));
+
+    let mut json = match route.body.as_deref() {
+        Some(_) if body.is_empty() => Map::new(),
+        Some("*") => match serde_json::from_slice(body).map_err(invalid)? {
+            Json::Object(map) => map,
+            _ => return Err(GatewayError::InvalidRequestError("expected a JSON object".to_string())),
+        },
+        Some(field) => Map::from_iter([(field.to_string(), serde_json::from_slice(body).map_err(invalid)?)]),
+        None => Map::new(),
+    };
+
+    for (field_path, value) in bindings {
+        let mut target = &mut json;
+        let mut names = field_path.split('.').peekable();
+        while let Some(name) = names.next() {
+            if names.peek().is_none() {
+                target.insert(name.to_string(), Json::String(value));
+                break;
+            }
+            let nested = target
+                .entry(name.to_string())
+                .or_insert_with(|| Json::Object(Map::new()));
+            target = match nested {
+                Json::Object(map) => map,
+                _ => return Err(GatewayError::InvalidRequestError(format!("'{}' is not a message", name))),
+            };
+        }
+    }
+
+    DynamicMessage::deserialize(route.grpc_method.input(), Json::Object(json))
+        .map_err(|e| GatewayError::InvalidRequestError(e.to_string()))
+}
+
+fn decode_reply(route: &TranscodedRoute, mut body: Bytes) -> HandlerResult<DynamicMessage> {
+    let malformed = || GatewayError::InternalError("malformed gRPC reply".to_string());
+
+    if body.remaining() < 5 {
+        return Err(malformed());
+    }
+    if body.get_u8() != 0 {
+        return Err(GatewayError::InternalError("compressed gRPC replies are not supported".to_string()));
+    }
+    let len = body.get_u32() as usize;
+    if body.remaining() < len {
+        return Err(malformed());
+    }
+
+    DynamicMessage::decode(route.grpc_method.output(), body.slice(..len))
+        .map_err(|e| GatewayError::InternalError(format!("failed to decode gRPC reply: {}", e)))
+}
+
+/// Routes declared with `option (google.api.http)` on methods of any loaded descriptor.
+fn annotated_routes(pool: &DescriptorPool) -> Result<Vec<TranscodedRoute>, GatewayError> {
+    let Some(extension) = pool.get_extension_by_name(HTTP_RULE_EXTENSION) else {
+        return Ok(Vec::new());
+    };
+
+    let mut routes = Vec::new();
+    for service in pool.services() {
+        for grpc_method in service.methods() {
+            let options = grpc_method.options();
+            if !options.has_extension(&extension) {
+                continue;
+            }
+            let Value::Message(rule) = options.get_extension(&extension).into_owned() else {
+                continue;
+            };
+
+            let body = match rule.get_field_by_name("body").as_deref() {
+                Some(Value::String(body)) if !body.is_empty() => Some(body.clone()),
+                _ => None,
+            };
+            for (name, method) in &HTTP_RULE_METHODS {
+                if let Some(Value::String(path)) = rule.get_field_by_name(name).as_deref() {
+                    if !path.is_empty() {
+                        routes.push(TranscodedRoute {
+                            method: method.clone(),
+                            template: parse_template(path)?,
+                            grpc_method: grpc_method.clone(),
+                            body: body.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(routes)
+}
+
+fn configured_route(pool: &DescriptorPool, config: &TranscodedRouteConfig) -> Result<TranscodedRoute, GatewayError> {
+    let method = Method::from_str(&config.method.to_uppercase())
+        .map_err(|_| GatewayError::ConfigError(format!("invalid HTTP method '{}'", config.method)))?;
+    let (service, name) = config
+        .grpc_method
+        .rsplit_once('.')
+        .ok_or_else(|| GatewayError::ConfigError(format!("invalid gRPC method '{}'", config.grpc_method)))?;
+    let grpc_method = pool
+        .get_service_by_name(service)
+        .and_then(|service| service.methods().find(|method| method.name() == name))
+        .ok_or_else(|| GatewayError::ConfigError(format!("unknown gRPC method '{}'", config.grpc_method)))?;
+
+    // Bodiless methods take the whole request from the path unless told otherwise
+    let body = match &config.body {
+        Some(body) if body.is_empty() => None,
+        Some(body) => Some(body.clone()),
+        None if method == Method::GET || method == Method::DELETE => None,
+        None => Some("*".to_string()),
+    };
+
+    Ok(TranscodedRoute {
+        method,
+        template: parse_template(&config.path)?,
+        grpc_method,
+        body,
+    })
+}
+
+/// Parses the subset of the `google.api.http` template syntax the gateway supports: literal
+/// segments and single-segment variables, written `{field}` or `{field=*}`.
+fn parse_template(path: &str) -> Result<Vec<Segment>, GatewayError> {
+    let unsupported = || GatewayError::ConfigError(format!("unsupported path template '{}'", path));
+
+    path.strip_prefix('/')
+        .ok_or_else(unsupported)?
+        .split('/')
+        .map(|segment| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(variable) => match variable.split_once('=') {
+                None => Ok(Segment::Field(variable.to_string())),
+                Some((field, "*")) => Ok(Segment::Field(field.to_string())),
+                Some(_) => Err(unsupported()),
+            },
+            None if segment.contains(['{', '}', '*', ':']) => Err(unsupported()),
+            None => Ok(Segment::Literal(segment.to_string())),
+        })
+        .collect()
+}
+
+fn match_template(template: &[Segment], path: &str) -> Option<Vec<(String, String)>> {
+    let segments: Vec<&str> = path.strip_prefix('/')?.split('/').collect();
+    if segments.len() != template.len() {
+        return None;
+    }
+
+    let mut bindings = Vec::new();
+    for (segment, expected) in segments.into_iter().zip(template) {
+        match expected {
+            Segment::Literal(literal) if literal == segment => {}
+            Segment::Field(field) if !segment.is_empty() => {
+                let value = percent_decode_str(segment).decode_utf8().ok()?;
+                bindings.push((field.clone(), value.into_owned()));
+            }
+            _ => return None,
+        }
+    }
+    Some(bindings)
+}
+
+fn template_string(template: &[Segment]) -> String {
+    template
+        .iter()
+        .map(|segment| match segment {
+            Segment::Literal(literal) => format!("/{}", literal),
+            Segment::Field(field) => format!("/{{{}}}", field),
+        })
+        .collect()
+}
+
+fn grpc_path(method: &MethodDescriptor) -> String {
+    format!("/{}/{}", method.parent_service().full_name(), method.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+    use prost_reflect::prost_types::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet, MethodDescriptorProto,
+        ServiceDescriptorProto,
+    };
+
+    /// Path variable bindings as written in the test tables.
+    type Vars = &'static [(&'static str, &'static str)];
+
+    fn field(name: &str, number: i32, kind: Type, type_name: Option<&str>) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            json_name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(kind as i32),
+            type_name: type_name.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    /// `test.Things/Get` taking `Outer { string id; Inner inner; int64 size; bool flag; }` with
+    /// `Inner { int32 count; string label; }`.
+    fn get_method() -> MethodDescriptor {
+        let file = FileDescriptorProto {
+            name: Some("test.proto".to_string()),
+            package: Some("test".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Inner".to_string()),
+                    field: vec![
+                        field("count", 1, Type::Int32, None),
+                        field("label", 2, Type::String, None),
+                    ],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Outer".to_string()),
+                    field: vec![
+                        field("id", 1, Type::String, None),
+                        field("inner", 2, Type::Message, Some(".test.Inner")),
+                        field("size", 3, Type::Int64, None),
+                        field("flag", 4, Type::Bool, None),
+                    ],
+                    ..Default::default()
+                },
+            ],
+            service: vec![ServiceDescriptorProto {
+                name: Some("Things".to_string()),
+                method: vec![MethodDescriptorProto {
+                    name: Some("Get".to_string()),
+                    input_type: Some(".test.Outer".to_string()),
+                    output_type: Some(".test.Outer".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let pool = DescriptorPool::from_file_descriptor_set(FileDescriptorSet { file: vec![file] }).unwrap();
+        pool.get_service_by_name("test.Things").unwrap().methods().next().unwrap()
+    }
+
+    fn route(path: &str, body: Option<&str>) -> TranscodedRoute {
+        TranscodedRoute {
+            method: Method::POST,
+            template: parse_template(path).unwrap(),
+            grpc_method: get_method(),
+            body: body.map(str::to_string),
+        }
+    }
+
+    fn bindings(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn parse_template_accepts_literals_and_single_segment_variables() {
+        let cases = [
+            ("/v1/things", "/v1/things"),
+            ("/v1/things/{id}", "/v1/things/{id}"),
+            ("/v1/things/{id=*}", "/v1/things/{id}"),
+            ("/v1/{inner.label}/items/{id}", "/v1/{inner.label}/items/{id}"),
+        ];
+        for (path, expected) in cases {
+            let template = parse_template(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            assert_eq!(template_string(&template), expected);
+        }
+    }
+
+    #[test]
+    fn parse_template_rejects_unsupported_syntax() {
+        let cases = [
+            "v1/things",
+            "/v1/things/{id=**}",
+            "/v1/things/{id=things/*}",
+            "/v1/things/*",
+            "/v1/things:batchGet",
+            "/v1/{id",
+        ];
+        for path in cases {
+            assert!(
+                matches!(parse_template(path), Err(GatewayError::ConfigError(_))),
+                "{} should be rejected",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn match_template_binds_variables() {
+        let template = parse_template("/v1/things/{id}/labels/{inner.label}").unwrap();
+        let cases: [(&str, Option<Vars>); 7] = [
+            ("/v1/things/42/labels/red", Some(&[("id", "42"), ("inner.label", "red")])),
+            ("/v1/things/a%20b/labels/%E2%9C%93", Some(&[("id", "a b"), ("inner.label", "✓")])),
+            ("/v1/things/42/labels", None),
+            ("/v1/things/42/labels/red/extra", None),
+            ("/v1/other/42/labels/red", None),
+            ("/v1/things//labels/red", None),
+            ("/v1/things/%FF/labels/red", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                match_template(&template, path),
+                expected.map(bindings),
+                "path {}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn build_request_merges_body_and_path_variables() {
+        let cases: [(&str, Option<&str>, Vars, &str, Json); 6] = [
+            ("/things/{id}", None, &[("id", "42")], "", serde_json::json!({"id": "42"})),
+            (
+                "/things/{id}/{inner.label}",
+                None,
+                &[("id", "42"), ("inner.label", "red")],
+                "",
+                serde_json::json!({"id": "42", "inner": {"label": "red"}}),
+            ),
+            (
+                "/things/{id}",
+                Some("*"),
+                &[("id", "from-path")],
+                r#"{"id": "from-body", "size": "7", "flag": true}"#,
+                serde_json::json!({"id": "from-path", "size": "7", "flag": true}),
+            ),
+            (
+                "/things/{inner.label}",
+                Some("*"),
+                &[("inner.label", "red")],
+                r#"{"inner": {"count": 3}}"#,
+                serde_json::json!({"inner": {"count": 3, "label": "red"}}),
+            ),
+            (
+                "/things/{id}",
+                Some("inner"),
+                &[("id", "42")],
+                r#"{"count": 3, "label": "red"}"#,
+                serde_json::json!({"id": "42", "inner": {"count": 3, "label": "red"}}),
+            ),
+            (
+                "/things/{size}/{inner.count}",
+                None,
+                &[("size", "9000000000"), ("inner.count", "5")],
+                "",
+                serde_json::json!({"size": "9000000000", "inner": {"count": 5}}),
+            ),
+        ];
+        for (path, body_field, vars, body, expected) in cases {
+            let route = route(path, body_field);
+            let message = build_request(&route, bindings(vars), &Bytes::from(body.to_string()))
+                .unwrap_or_else(|e| panic!("{} {}: {}", path, body, e));
+            assert_eq!(serde_json::to_value(&message).unwrap(), expected, "{} {}", path, body);
+        }
+    }
+
+    #[test]
+    fn build_request_rejects_invalid_input() {
+        let cases: [(&str, Option<&str>, Vars, &str); 9] = [
+            ("/things", Some("*"), &[], r#"{"unknown": 1}"#),
+            ("/things", Some("*"), &[], r#"{"size": "many"}"#),
+            ("/things", Some("*"), &[], r#"{"flag": "yes"}"#),
+            ("/things", Some("*"), &[], r#"{"inner": {"count": 1.5}}"#),
+            ("/things", Some("*"), &[], r#"[1, 2]"#),
+            ("/things", Some("*"), &[], r#"{"id": "#),
+            ("/things/{size}", None, &[("size", "big")], ""),
+            ("/things/{id.part}", None, &[("id.part", "x")], ""),
+            ("/things/{inner.label}", Some("*"), &[("inner.label", "red")], r#"{"inner": 5}"#),
+        ];
+        for (path, body_field, vars, body) in cases {
+            let route = route(path, body_field);
+            assert!(
+                matches!(
+                    build_request(&route, bindings(vars), &Bytes::from(body.to_string())),
+                    Err(GatewayError::InvalidRequestError(_))
+                ),
+                "{} {} should be rejected",
+                path,
+                body
+            );
+        }
+    }
+}
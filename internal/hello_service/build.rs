@@ -1,3 +1,10 @@
+use std::path::PathBuf;
+
 fn main() {
-    tonic_build::compile_protos("../../proto/hello.proto").unwrap();
+    // The descriptor set feeds gRPC server reflection
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("hello_descriptor.bin"))
+        .compile_protos(&["../../proto/hello.proto"], &["../../proto"])
+        .unwrap();
 }
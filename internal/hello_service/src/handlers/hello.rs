@@ -2,6 +2,9 @@ use crate::hello::hello_service_server::HelloService;
 use crate::hello::{HelloRequest, HelloResponse};
 use tonic::{Request, Response, Status};
 
+// Set by the gateway after it has validated the caller's access token
+const USER_ID_METADATA: &str = "x-user-id";
+
 #[derive(Debug, Default)]
 pub struct MyHelloService {}
 
@@ -11,6 +14,14 @@ impl HelloService for MyHelloService {
         &self,
         request: Request<HelloRequest>,
     ) -> Result<Response<HelloResponse>, Status> {
+        let caller = request
+            .metadata()
+            .get(USER_ID_METADATA)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("anonymous")
+            .to_string();
+        log::info!("SayHello from user {}", caller);
+
         let name = request.into_inner().name;
         let name_lowercase = name.to_lowercase();
 
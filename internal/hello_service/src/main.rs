@@ -8,10 +8,14 @@ use std::error::Error;
 
 pub mod hello {
     tonic::include_proto!("hello");
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("hello_descriptor");
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    grpc_services::install_crypto_provider()?;
+
     // Просто указываем путь к конфигу
     let config = load_config("hello_service")?; // <-- здесь путь к папке конфига: configs/hello_service/config.toml
 
@@ -1,19 +1,74 @@
 use crate::handlers::hello::MyHelloService;
 use crate::hello::hello_service_server::HelloServiceServer;
+use crate::hello::FILE_DESCRIPTOR_SET;
 use config::config::AppConfig;
+use grpc_services::{server_tls_config, standard_services, ServiceHealth};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::oneshot;
 use tonic::transport::Server;
 
 use std::error::Error;
+use std::time::Duration;
+
+// Long enough for load balancers and the gateway's health checks to notice NOT_SERVING
+const DEFAULT_DRAIN_PERIOD: Duration = Duration::from_secs(5);
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(20);
 
 pub async fn run_server(config: AppConfig) -> Result<(), Box<dyn Error>> {
     let hello_service = MyHelloService::default();
+    let (health, standard_routes) = standard_services(&[FILE_DESCRIPTOR_SET])?;
+    health.set_serving::<HelloServiceServer<MyHelloService>>().await;
+    let shutdown = config.shutdown.as_ref();
+    let drain_period = shutdown
+        .and_then(|shutdown| shutdown.drain_period_secs)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DRAIN_PERIOD);
+    let drain_timeout = shutdown
+        .and_then(|shutdown| shutdown.drain_timeout_secs)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DRAIN_TIMEOUT);
+    let mut sigterm = signal(SignalKind::terminate())?;
 
     println!("{} running on {}", config.service_name, config.address);
 
-    Server::builder()
+    let mut server = Server::builder();
+    if let Some(tls) = server_tls_config(&config)? {
+        log::info!("Serving gRPC over TLS");
+        server = server.tls_config(tls)?;
+    }
+
+    let (closed_tx, closed_rx) = oneshot::channel();
+    let serve = server
+        .add_routes(standard_routes)
         .add_service(HelloServiceServer::new(hello_service))
-        .serve(config.address)
-        .await?;
+        .serve_with_shutdown(config.address, async move {
+            tokio::select! {
+                _ = sigterm.recv() => log::info!("SIGTERM received"),
+                _ = tokio::signal::ctrl_c() => log::info!("SIGINT received"),
+            }
+            drain(health, drain_period).await;
+            let _ = closed_tx.send(());
+        });
+
+    // Tonic waits for the requests in flight without a limit once the listener is closed
+    tokio::select! {
+        result = serve => result?,
+        Ok(()) = async move {
+            closed_rx.await?;
+            tokio::time::sleep(drain_timeout).await;
+            Ok::<_, oneshot::error::RecvError>(())
+        } => log::warn!("Requests still in flight after {:?}, stopping anyway", drain_timeout),
+    }
 
+    log::info!("{} stopped", config.service_name);
     Ok(())
 }
+
+/// Reports NOT_SERVING and keeps serving for `drain_period`, so traffic is steered away before
+/// the listener closes; the requests still in flight then get up to the drain timeout.
+async fn drain(health: ServiceHealth, drain_period: Duration) {
+    health.set_all_not_serving().await;
+
+    log::info!("Health set to NOT_SERVING, closing in {:?}", drain_period);
+    tokio::time::sleep(drain_period).await;
+}
@@ -15,17 +15,163 @@ pub struct RawAuthServiceConfig {
     pub address: String, // Просто строка (gRPC клиент требует String)
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RawHelloServiceConfig {
+    pub address: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RawTlsConfig {
     pub cert_path: Option<String>,
+    pub chain_path: Option<String>, // промежуточные сертификаты, если их нет в cert_path
+    pub key_path: Option<String>,   // PKCS#8, PKCS#1 (RSA) или SEC1 (EC); можно зашифрованный PKCS#8
+    pub key_passphrase_env: Option<String>, // имя переменной окружения с паролем ключа
+    #[serde(default)]
+    pub dev_self_signed: bool, // только для разработки: самоподписанный сертификат, если пути не заданы
+    #[serde(default = "default_true")]
+    pub watch: bool, // перечитывать сертификат при изменении файлов (SIGHUP работает всегда)
+    pub client_ca_path: Option<String>, // CA для проверки клиентских сертификатов (mTLS)
+    pub client_auth: Option<ClientAuth>, // "required" или "optional", по умолчанию "required"
+}
+
+// Требовать ли клиентский сертификат, когда задан client_ca_path
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    #[default]
+    Required,
+    Optional, // без сертификата пускаем, присланный — проверяем
+}
+
+// TLS от шлюза к gRPC-сервисам; адреса сервисов при этом должны быть https://
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpstreamTlsConfig {
+    pub ca_path: String,           // CA, которым подписаны сертификаты сервисов; обязателен
+    pub cert_path: Option<String>, // клиентский сертификат шлюза для mTLS
+    pub key_path: Option<String>,
+    pub domain: Option<String>,    // переопределение SNI / имени в сертификате
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpstreamConnectConfig {
+    pub connect_timeout_secs: Option<u64>, // таймаут одной попытки соединения с сервисом
+    pub initial_backoff_ms: Option<u64>,   // пауза после первой неудачи, дальше удваивается
+    pub max_backoff_secs: Option<u64>,     // верхняя граница паузы; сбрасывается успешным соединением или health-check
+}
+
+// Одинаково для шлюза и gRPC-сервисов: сначала drain_period, затем drain_timeout
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShutdownConfig {
+    pub drain_period_secs: Option<u64>, // сколько сообщать о неготовности (/readyz 503, NOT_SERVING), продолжая принимать запросы
+    pub drain_timeout_secs: Option<u64>, // сколько после закрытия листенеров ждать запросы в полёте
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthCheckConfig {
+    pub interval_secs: Option<u64>, // как часто опрашивать grpc.health.v1.Health у сервисов
+    pub timeout_secs: Option<u64>,  // сколько ждать ответа на одну проверку
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListenerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub address: Option<SocketAddr>, // по умолчанию — адрес из [server]
+    pub cert_path: Option<String>,   // цепочка сертификатов, по умолчанию из [tls]
+    pub chain_path: Option<String>,
     pub key_path: Option<String>,
+    pub alpn: Option<Vec<String>>,   // по умолчанию "h2" / "h3"
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawStorageConfig {
+    pub backend: String, // "memory" или "sqlite"
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    pub algorithm: String, // HS256, RS256, EdDSA, ...
+    pub secret: Option<String>,
+    pub secret_env: Option<String>, // имя переменной окружения с секретом
+    pub private_key_path: Option<String>,
+    pub public_key_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwtConfig {
+    pub signing_kid: String, // ключ, которым подписываются новые токены
+    pub access_token_ttl_secs: Option<u64>,
+    pub keys: Vec<JwtKeyConfig>, // все ключи, по которым ещё принимаются токены
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ValidateCacheConfig {
+    pub capacity: Option<usize>,
+    pub ttl_secs: Option<u64>,          // верхняя граница жизни положительного ответа
+    pub negative_ttl_secs: Option<u64>, // сколько помнить отвергнутые токены
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrpcRouteConfig {
+    pub prefix: String,   // например "/hello.HelloService/*"
+    pub upstream: String, // адрес сервиса, "http://127.0.0.1:50051"
+    #[serde(default)]
+    pub authenticated: bool,
+    #[serde(default)]
+    pub roles: Vec<String>, // роли, без которых запрос не пропускается
+    #[serde(default)]
+    pub client_certificate: bool, // вместо токена — проверенный клиентский сертификат (mTLS)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TranscodedRouteConfig {
+    pub method: String,      // HTTP метод: GET, POST, ...
+    pub path: String,        // шаблон пути, например "/v1/hello/{name}"
+    pub grpc_method: String, // полное имя метода, "hello.HelloService.SayHello"
+    pub body: Option<String>, // "*" — всё тело в запрос, имя поля — только в это поле
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TranscodingConfig {
+    #[serde(default)]
+    pub descriptor_sets: Vec<String>, // дополнительные FileDescriptorSet файлы
+    #[serde(default)]
+    pub routes: Vec<TranscodedRouteConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GrpcWebConfig {
+    #[serde(default)]
+    pub enabled: bool, // по умолчанию выключен
+    #[serde(default)]
+    pub allowed_origins: Vec<String>, // обязателен, если enabled = true
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RawConfig {
     pub server: RawServerConfig,
     pub tls: Option<RawTlsConfig>,
+    pub http2: Option<ListenerConfig>,
+    pub http3: Option<ListenerConfig>,
+    pub upstream_tls: Option<UpstreamTlsConfig>,
+    pub upstream_connect: Option<UpstreamConnectConfig>,
+    pub shutdown: Option<ShutdownConfig>,
+    pub health: Option<HealthCheckConfig>,
     pub auth_service: Option<RawAuthServiceConfig>, // Добавить это поле
+    pub hello_service: Option<RawHelloServiceConfig>,
+    pub storage: Option<RawStorageConfig>,
+    pub jwt: Option<JwtConfig>,
+    pub validate_cache: Option<ValidateCacheConfig>,
+    #[serde(default)]
+    pub grpc_routes: Vec<GrpcRouteConfig>,
+    pub transcoding: Option<TranscodingConfig>,
+    pub grpc_web: Option<GrpcWebConfig>,
 }
 
 #[derive(Debug)]
@@ -35,7 +181,27 @@ pub struct AppConfig {
     pub log_level: String,
     pub tls_cert_path: Option<String>,
     pub tls_key_path: Option<String>,
+    pub tls_chain_path: Option<String>,
+    pub tls_key_passphrase_env: Option<String>,
+    pub tls_dev_self_signed: bool,
+    pub tls_watch: bool,
+    pub tls_client_ca_path: Option<String>,
+    pub tls_client_auth: ClientAuth,
+    pub http2: Option<ListenerConfig>,
+    pub http3: Option<ListenerConfig>,
+    pub upstream_tls: Option<UpstreamTlsConfig>,
+    pub upstream_connect: Option<UpstreamConnectConfig>,
+    pub shutdown: Option<ShutdownConfig>,
+    pub health: Option<HealthCheckConfig>,
     pub auth_service_address: Option<String>,  // Добавьте это поле
+    pub hello_service_address: Option<String>,
+    pub storage_backend: Option<String>,
+    pub storage_path: Option<String>,
+    pub jwt: Option<JwtConfig>,
+    pub validate_cache: Option<ValidateCacheConfig>,
+    pub grpc_routes: Vec<GrpcRouteConfig>,
+    pub transcoding: Option<TranscodingConfig>,
+    pub grpc_web: Option<GrpcWebConfig>,
 }
 
 
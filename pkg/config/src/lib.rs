@@ -1,5 +1,9 @@
 pub mod config;
 pub mod settings;
 
-pub use config::{AppConfig, RawConfig};
+pub use config::{
+    AppConfig, ClientAuth, GrpcRouteConfig, GrpcWebConfig, HealthCheckConfig, JwtConfig, JwtKeyConfig, ListenerConfig,
+    RawConfig, ShutdownConfig, TranscodedRouteConfig, TranscodingConfig, UpstreamConnectConfig, UpstreamTlsConfig,
+    ValidateCacheConfig,
+};
 pub use settings::load_config;
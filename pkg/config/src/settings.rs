@@ -4,8 +4,8 @@ use std::error::Error;
 pub fn load_config(service_name: &str) -> Result<AppConfig, Box<dyn Error>> {
     let config_path = format!("configs/{}/config", service_name);
 
-    let settings = ::config::Config::builder()
-        .add_source(::config::File::with_name(&config_path).required(true))
+    let settings = config_rs::Config::builder()
+        .add_source(config_rs::File::with_name(&config_path).required(true))
         .build()?;
 
     let raw_config: RawConfig = settings.try_deserialize()?;
@@ -15,7 +15,27 @@ pub fn load_config(service_name: &str) -> Result<AppConfig, Box<dyn Error>> {
         service_name: raw_config.server.name,
         log_level: raw_config.server.log_level,
         auth_service_address: raw_config.auth_service.map(|a| a.address),
+        hello_service_address: raw_config.hello_service.map(|h| h.address),
         tls_cert_path: raw_config.tls.as_ref().and_then(|t| t.cert_path.clone()),
         tls_key_path: raw_config.tls.as_ref().and_then(|t| t.key_path.clone()),
+        tls_chain_path: raw_config.tls.as_ref().and_then(|t| t.chain_path.clone()),
+        tls_key_passphrase_env: raw_config.tls.as_ref().and_then(|t| t.key_passphrase_env.clone()),
+        tls_dev_self_signed: raw_config.tls.as_ref().is_some_and(|t| t.dev_self_signed),
+        tls_watch: raw_config.tls.as_ref().is_none_or(|t| t.watch),
+        tls_client_ca_path: raw_config.tls.as_ref().and_then(|t| t.client_ca_path.clone()),
+        tls_client_auth: raw_config.tls.as_ref().and_then(|t| t.client_auth).unwrap_or_default(),
+        http2: raw_config.http2,
+        http3: raw_config.http3,
+        upstream_tls: raw_config.upstream_tls,
+        upstream_connect: raw_config.upstream_connect,
+        shutdown: raw_config.shutdown,
+        health: raw_config.health,
+        storage_backend: raw_config.storage.as_ref().map(|s| s.backend.clone()),
+        storage_path: raw_config.storage.as_ref().and_then(|s| s.path.clone()),
+        jwt: raw_config.jwt,
+        validate_cache: raw_config.validate_cache,
+        grpc_routes: raw_config.grpc_routes,
+        transcoding: raw_config.transcoding,
+        grpc_web: raw_config.grpc_web,
     })
 }
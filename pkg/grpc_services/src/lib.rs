@@ -0,0 +1,64 @@
+pub mod tls;
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use tonic::server::NamedService;
+use tonic::service::Routes;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
+pub use tls::{install_crypto_provider, server_tls_config, TlsError};
+pub use tonic_reflection::server::Error as ReflectionError;
+
+/// `grpc.health.v1.Health` and server reflection (v1 and v1alpha) for a tonic server, so
+/// `grpcurl` and Kubernetes gRPC probes work against it.
+///
+/// `file_descriptor_sets` are the sets `tonic_build` writes with `file_descriptor_set_path`;
+/// the health and reflection protos are always included.
+pub fn standard_services(file_descriptor_sets: &[&[u8]]) -> Result<(ServiceHealth, Routes), ReflectionError> {
+    let (reporter, health_service) = tonic_health::server::health_reporter();
+
+    let reflection = || {
+        file_descriptor_sets.iter().fold(
+            tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET),
+            |builder, set| builder.register_encoded_file_descriptor_set(set),
+        )
+    };
+
+    let mut routes = Routes::builder();
+    routes
+        .add_service(health_service)
+        .add_service(reflection().build_v1()?)
+        .add_service(reflection().build_v1alpha()?);
+
+    let health = ServiceHealth {
+        reporter,
+        services: Arc::default(),
+    };
+    Ok((health, routes.routes()))
+}
+
+/// Per-service serving status reported by the health service. The server as a whole (the
+/// empty service name) starts out SERVING.
+#[derive(Clone)]
+pub struct ServiceHealth {
+    reporter: HealthReporter,
+    services: Arc<Mutex<BTreeSet<&'static str>>>,
+}
+
+impl ServiceHealth {
+    pub async fn set_serving<S: NamedService>(&self) {
+        self.services.lock().unwrap().insert(S::NAME);
+        self.reporter.set_serving::<S>().await;
+    }
+
+    /// Reports the server and every service it has seen as NOT_SERVING, e.g. when shutting down.
+    pub async fn set_all_not_serving(&self) {
+        let services: Vec<_> = self.services.lock().unwrap().iter().copied().collect();
+        for service in services {
+            self.reporter.set_service_status(service, ServingStatus::NotServing).await;
+        }
+        self.reporter.set_service_status("", ServingStatus::NotServing).await;
+    }
+}
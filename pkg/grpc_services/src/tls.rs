@@ -0,0 +1,112 @@
+use config::{AppConfig, ClientAuth};
+use thiserror::Error;
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("failed to install the rustls crypto provider")]
+    CryptoProvider,
+
+    #[error("incomplete [tls] section: {0}")]
+    Incomplete(&'static str),
+
+    #[error("failed to read {path}: {source}")]
+    Read {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// Tonic builds its TLS configs from the process-wide rustls crypto provider, so this must run
+/// before any of them is created.
+pub fn install_crypto_provider() -> Result<(), TlsError> {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .map_err(|_| TlsError::CryptoProvider)
+}
+
+/// `[tls]` with a certificate and key turns on TLS; a `client_ca_path` additionally requires
+/// callers (the gateway) to present a certificate signed by that CA, or only verifies one when
+/// `client_auth = "optional"`. A section with only some of them is an error rather than a
+/// silent fallback to plaintext.
+pub fn server_tls_config(config: &AppConfig) -> Result<Option<ServerTlsConfig>, TlsError> {
+    build_server_tls_config(
+        config.tls_cert_path.as_deref(),
+        config.tls_key_path.as_deref(),
+        config.tls_client_ca_path.as_deref(),
+        config.tls_client_auth,
+    )
+}
+
+fn build_server_tls_config(
+    cert_path: Option<&str>,
+    key_path: Option<&str>,
+    client_ca_path: Option<&str>,
+    client_auth: ClientAuth,
+) -> Result<Option<ServerTlsConfig>, TlsError> {
+    let (cert_path, key_path) = match (cert_path, key_path, client_ca_path) {
+        (None, None, None) => return Ok(None),
+        (Some(cert_path), Some(key_path), _) => (cert_path, key_path),
+        (None, None, Some(_)) => return Err(TlsError::Incomplete("client_ca_path needs cert_path and key_path")),
+        _ => return Err(TlsError::Incomplete("cert_path and key_path must be set together")),
+    };
+
+    let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(read(cert_path)?, read(key_path)?));
+    if let Some(ca_path) = client_ca_path {
+        tls = tls
+            .client_ca_root(Certificate::from_pem(read(ca_path)?))
+            .client_auth_optional(client_auth == ClientAuth::Optional);
+    }
+    Ok(Some(tls))
+}
+
+fn read(path: &str) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|source| TlsError::Read {
+        path: path.to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../cert.pem");
+    const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../key.pem");
+
+    type Built = Result<Option<ServerTlsConfig>, TlsError>;
+
+    fn build(cert: Option<&str>, key: Option<&str>, client_ca: Option<&str>) -> Built {
+        build_server_tls_config(cert, key, client_ca, ClientAuth::Required)
+    }
+
+    #[test]
+    fn absent_section_serves_plaintext() {
+        assert!(build(None, None, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn complete_section_enables_tls() {
+        assert!(build(Some(CERT), Some(KEY), None).unwrap().is_some());
+        assert!(build(Some(CERT), Some(KEY), Some(CERT)).unwrap().is_some());
+    }
+
+    #[test]
+    fn partial_sections_are_rejected() {
+        for (cert, key, client_ca) in [
+            (Some(CERT), None, None),
+            (None, Some(KEY), None),
+            (Some(CERT), None, Some(CERT)),
+            (None, None, Some(CERT)),
+        ] {
+            assert!(
+                matches!(build(cert, key, client_ca), Err(TlsError::Incomplete(_))),
+                "cert {:?}, key {:?}, client_ca {:?}",
+                cert,
+                key,
+                client_ca
+            );
+        }
+    }
+}